@@ -0,0 +1,111 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::date::{Date, Zone};
+
+/// Source of the current time, so "today" can be swapped out in tests.
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+
+    fn zone(&self) -> Zone;
+
+    fn today(&self) -> Date {
+        self.zone().date_at(self.now())
+    }
+
+    fn yesterday(&self) -> Date {
+        self.zone().date_at(self.now() - Duration::days(1))
+    }
+
+    /// Earliest date accepted on the command line.
+    fn min_date(&self) -> Date {
+        MIN_DATE
+    }
+
+    /// Latest date accepted on the command line, the end of next year by default.
+    fn max_date(&self) -> Date {
+        default_max_date(&self.today())
+    }
+}
+
+/// Default of `--min-date`.
+pub const MIN_DATE: Date = Date { year: 1970, month: 1, day: 1 };
+
+fn default_max_date(today: &Date) -> Date {
+    Date { year: today.year + 1, month: 12, day: 31 }
+}
+
+pub struct SystemClock {
+    pub zone: Zone,
+    pub min_date: Option<Date>,
+    pub max_date: Option<Date>,
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn zone(&self) -> Zone {
+        self.zone
+    }
+
+    fn min_date(&self) -> Date {
+        self.min_date.clone().unwrap_or(MIN_DATE)
+    }
+
+    fn max_date(&self) -> Date {
+        match &self.max_date {
+            Some(date) => date.clone(),
+            None => default_max_date(&self.today()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub struct FixedClock {
+    pub now: DateTime<Utc>,
+    pub zone: Zone,
+}
+
+#[cfg(test)]
+impl FixedClock {
+    /// A clock fixed at noon UTC on the given day.
+    pub fn at(year: i32, month: u32, day: u32) -> FixedClock {
+        use chrono::TimeZone;
+
+        FixedClock {
+            now: Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap(),
+            zone: Zone::Utc,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now
+    }
+
+    fn zone(&self) -> Zone {
+        self.zone
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixed_clock() {
+        let clock = FixedClock::at(2024, 3, 1);
+
+        assert_eq!(clock.today(), Date { year: 2024, month: 3, day: 1 });
+        assert_eq!(clock.yesterday(), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(clock.min_date(), MIN_DATE);
+        assert_eq!(clock.max_date(), Date { year: 2025, month: 12, day: 31 });
+
+        let clock = SystemClock { zone: Zone::Utc, min_date: Some(Date { year: 2000, month: 1, day: 1 }), max_date: None };
+        assert_eq!(clock.min_date(), Date { year: 2000, month: 1, day: 1 });
+        assert_eq!(clock.max_date().year, clock.today().year + 1);
+    }
+}
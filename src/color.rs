@@ -0,0 +1,158 @@
+use crate::{config::PaletteNames, error::CliError};
+
+/// Supported color names and their ANSI foreground codes.
+pub const COLORS: [(&str, &str); 8] = [
+    ("black", "30"),
+    ("red", "31"),
+    ("green", "32"),
+    ("yellow", "33"),
+    ("blue", "34"),
+    ("magenta", "35"),
+    ("cyan", "36"),
+    ("white", "37"),
+];
+
+pub fn ansi_code(color: &str) -> Option<&'static str> {
+    COLORS.iter()
+        .find(|(name, _)| *name == color)
+        .map(|(_, code)| *code)
+}
+
+pub fn validate(color: &str) -> Result<(), CliError> {
+
+    if ansi_code(color).is_some() {
+        return Ok(());
+    }
+
+    let names = COLORS.iter().map(|(name, _)| *name).collect::<Vec<&str>>();
+    Err(CliError(format!("invalid color {}, expected one of {}", color, names.join(", "))))
+}
+
+/// Wraps `text` in the ANSI escape codes for `color`, unchanged if the color is unknown.
+pub fn paint(text: &str, color: &str) -> String {
+    match ansi_code(color) {
+        Some(code) => paint_code(text, code),
+        None => text.to_owned(),
+    }
+}
+
+/// Wraps `text` in the ANSI escape codes for an already looked up color code.
+pub fn paint_code(text: &str, code: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// ANSI codes of the list grid, from the `palette.*` settings. `None` keeps the plain look.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Palette {
+    /// Marks of habits without a color of their own.
+    pub marked: Option<&'static str>,
+    /// Missed days.
+    pub empty: Option<&'static str>,
+    /// Missed days on weekends, instead of `empty`.
+    pub weekend: Option<&'static str>,
+}
+
+impl Palette {
+
+    /// Looks up the configured color names, unknown names are left out and
+    /// returned as warnings.
+    pub fn parse(names: &PaletteNames) -> (Palette, Vec<String>) {
+
+        let mut warnings = vec![];
+        let mut code = |key: &str, name: &Option<String>| {
+            let name = name.as_deref()?;
+            if let Err(err) = validate(name) {
+                warnings.push(format!("ignoring palette.{}: {}", key, err));
+            }
+            ansi_code(name)
+        };
+
+        let palette = Palette {
+            marked: code("marked", &names.marked),
+            empty: code("empty", &names.empty),
+            weekend: code("weekend", &names.weekend),
+        };
+
+        (palette, warnings)
+    }
+
+    /// Code for a missed day.
+    pub fn missed(&self, weekend: bool) -> Option<&'static str> {
+        match weekend {
+            true => self.weekend.or(self.empty),
+            false => self.empty,
+        }
+    }
+}
+
+/// Marks `text` with reverse video, keeping any color inside it.
+pub fn accent(text: &str) -> String {
+    format!("\x1b[7m{}\x1b[27m", text)
+}
+
+/// When to use colors, from `--color`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Only on a terminal and if `NO_COLOR` is not set.
+    Auto,
+    /// Always, even with `NO_COLOR` set.
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub const NAMES: [&'static str; 3] = ["auto", "always", "never"];
+
+    pub fn parse(choice: &str) -> Result<ColorChoice, CliError> {
+        match choice {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(CliError(format!("invalid color choice {}, expected one of {}", choice, ColorChoice::NAMES.join(", ")))),
+        }
+    }
+
+    /// Whether to color output. `no_color` is the value of `NO_COLOR`,
+    /// which disables auto colors when set to anything non-empty.
+    pub fn use_color(&self, no_color: Option<&str>, is_terminal: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(|v| v.is_empty()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette() {
+        let names = PaletteNames {
+            marked: Some("green".to_owned()),
+            empty: Some("grey".to_owned()),
+            weekend: Some("blue".to_owned()),
+        };
+
+        let (palette, warnings) = Palette::parse(&names);
+        assert_eq!(palette, Palette { marked: Some("32"), empty: None, weekend: Some("34") });
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("ignoring palette.empty: invalid color grey"));
+
+        assert_eq!(palette.missed(true), Some("34"));
+        assert_eq!(palette.missed(false), None);
+        assert_eq!(Palette::parse(&PaletteNames::default()), (Palette::default(), vec![]));
+    }
+
+    #[test]
+    fn test_use_color() {
+        assert!(ColorChoice::Auto.use_color(None, true));
+        assert!(ColorChoice::Auto.use_color(Some(""), true));
+        assert!(!ColorChoice::Auto.use_color(Some("1"), true));
+        assert!(!ColorChoice::Auto.use_color(None, false));
+
+        assert!(ColorChoice::Always.use_color(Some("1"), false));
+        assert!(!ColorChoice::Never.use_color(None, true));
+    }
+}
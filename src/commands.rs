@@ -1,23 +1,94 @@
-use std::io::stdin;
+use std::{collections::{BTreeSet, HashMap}, env, fs, io::{self, stdin, stdout, BufRead, IsTerminal}, path::{Path, PathBuf}, process};
 
-use chrono::Datelike;
-use clap::{arg, ArgMatches, Command};
+use chrono::Weekday;
+use clap::{arg, parser::ValueSource, ArgAction, ArgMatches, Command};
 
-use crate::{date::{self, Date}, error::CliError, storage::Storage};
+use crate::{clock::{Clock, SystemClock}, color::{self, ColorChoice, Palette}, date::{self, Date, DateRange, DayFilter, Zone}, error::CliError, export, config, grid::{Calendar, GridRow, MonthGrid}, ical, locale::{self, Locale}, pager, stats::{self, Group, HabitStats, Overview, StatsSummary}, storage::{self, BatchSummary, HabitExport, Metric, Schedule, Storage}};
 
 
-pub fn cli(storage: &Storage) -> Result<(), CliError> {
+pub fn cli() -> Result<(), CliError> {
 
     let matches = create_commands().get_matches();
 
+    let db = matches.get_one::<String>("db").map_or("habits.db", |db| db.as_str());
+    if matches.get_flag("version") || matches!(matches.subcommand(), Some(("version", _))) {
+        return version_readonly(db);
+    }
+
+    let mut storage = match matches.get_flag("readonly") {
+        true => storage::connect_readonly(db)?,
+        false => storage::connect(db)?,
+    };
+    if matches.get_flag("explain") {
+        storage.set_explain(Box::new(|sql| eprintln!("{}", sql)));
+    }
+    storage.set_strict_dates(matches.get_flag("strict-dates"));
+    let storage = &storage;
+
+    let zone = if matches.get_flag("utc") {
+        Zone::Utc
+    } else if let Ok(tz) = env::var("HTRACKR_TZ") {
+        Zone::parse(&tz)?
+    } else {
+        Zone::Local
+    };
+    let bound = |name: &str| match matches.get_one::<String>(name) {
+        Some(date) => Date::from_string(date).map(Some),
+        None => Ok(None),
+    };
+    let clock = SystemClock { zone, min_date: bound("min-date")?, max_date: bound("max-date")? };
+    if clock.min_date() > clock.max_date() {
+        return Err(CliError::new("--min-date must not be after --max-date"));
+    }
+    let locale = Locale::parse(matches.get_one::<String>("locale").map_or("en", |l| l.as_str()))?;
+
+    if let Some(("batch", s)) = matches.subcommand() {
+        return batch(s, storage, &clock, locale);
+    }
+
+    dispatch(&matches, storage, &clock, locale)
+}
+
+fn dispatch(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock, locale: Locale) -> Result<(), CliError> {
+
     match matches.subcommand() {
-        Some(("list", s)) => list(s, storage),
-        Some(("create", s)) => create(s, storage),
-        Some(("delete", s)) => delete(s, storage),
+        Some(("list", s)) => list(s, storage, clock),
+        Some(("create", s)) => create(s, storage, clock),
+        Some(("delete", s)) => delete(s, storage, clock),
+        Some(("restore", s)) => restore(s, storage),
+        Some(("trash", s)) => trash(s, storage),
         Some(("rename", s)) => rename(s, storage),
         Some(("id", s)) => id(s, storage),
-        Some(("mark", s)) => mark(s, storage),
-        Some(("unmark", s)) => unmark(s, storage),
+        Some(("reorder", s)) => reorder(s, storage),
+        Some(("color", s)) => set_color(s, storage),
+        Some(("interval", s)) => set_interval(s, storage),
+        Some(("schedule", s)) => set_schedule(s, storage),
+        Some(("goal", s)) => set_goal(s, storage),
+        Some(("pause", s)) => pause(s, storage, clock),
+        Some(("resume", s)) => resume(s, storage, clock),
+        Some(("tag", s)) => tag(s, storage),
+        Some(("mark", s)) => mark(s, storage, clock),
+        Some(("unmark", s)) => unmark(s, storage, clock),
+        Some(("count", s)) => count(s, storage, clock),
+        Some(("show", s)) => show(s, storage, clock, locale),
+        Some(("activity", s)) => activity(s, storage, clock),
+        Some(("trend", s)) => trend(s, storage, clock),
+        Some(("today", s)) => today(s, storage, clock),
+        Some(("top", s)) => top(s, storage, clock),
+        Some(("status", _)) => status(storage, clock),
+        Some(("diff", s)) => diff(s, storage, clock),
+        Some(("note", s)) => note(s, storage, clock),
+        Some(("log", s)) => log(s, storage, clock, locale),
+        Some(("stats", s)) => stats(s, storage, clock, locale),
+        Some(("export", s)) => export(s, storage, clock),
+        Some(("import", s)) => import(s, storage),
+        Some(("reset", s)) => reset(s, storage),
+        Some(("config", s)) => config(s, storage),
+        Some(("backup", s)) => backup(s, storage),
+        Some(("compact", s)) => compact(s, storage),
+        Some(("doctor", s)) => doctor(s, storage),
+        Some(("replay", _)) => replay(storage),
+        Some(("version", _)) => version(storage),
 
         _ => Err(CliError::new("invalid command"))
     }
@@ -26,229 +97,2775 @@ pub fn cli(storage: &Storage) -> Result<(), CliError> {
 fn create_commands() -> Command {
 
     let short_date_help = "Optional date in YYYY-MM format";
-    let date_help = "Date in YYYY-MM-DD format, or yesterday (y)";
+    let date_help = "Date in YYYY-MM-DD format, yesterday (y), or a range YYYY-MM-DD..YYYY-MM-DD";
 
 
     Command::new("htrackr")
     .arg_required_else_help(true)
+        .disable_version_flag(true)
+        .arg(arg!(-V --version "Print version of htrackr, SQLite and the database schema"))
+        .arg(arg!(--db <FILE> "Database file").default_value("habits.db").global(true))
+        .arg(arg!(--locale <LOCALE> "Language of weekday and month names")
+            .value_parser(locale::LOCALES)
+            .default_value("en")
+            .global(true)
+        )
+        .arg(arg!(--explain "Print each SQL statement to stderr before it runs").global(true))
+        .arg(arg!(--readonly "Open the database read-only, commands that change it fail").global(true))
+        .arg(arg!(--"strict-dates" "Fail on malformed dates in the database instead of skipping them").global(true))
+        .arg(arg!(--utc "Use UTC instead of local time for today and yesterday (or set HTRACKR_TZ)").global(true))
+        .arg(arg!(--"min-date" <DATE> "Earliest date accepted in arguments, 1970-01-01 by default").global(true))
+        .arg(arg!(--"max-date" <DATE> "Latest date accepted in arguments, the end of next year by default").global(true))
         .subcommand(Command::new("list")
             .about("List habits for month")
                 .arg(arg!(-c --compact "Compact print")
                 .required(false)
             )
-            .arg(arg!(date: [DATE]).required(false).help(short_date_help))
+            .arg(arg!(--color [WHEN] "Show marks in each habit's color, auto skips it when piped or NO_COLOR is set")
+                .value_parser(ColorChoice::NAMES)
+                .default_value("never")
+                .default_missing_value("auto")
+                .require_equals(true)
+            )
+            .arg(arg!(--"group-by-tag" "Group habits under their tags").conflicts_with("format"))
+            .arg(arg!(--"compact-numbers" "Only list the marked day numbers of each habit")
+                .conflicts_with_all(["format", "group-by-tag", "weekly"])
+            )
+            .arg(arg!(--"name-width" <N> "Width of the name column, longer names are truncated")
+                .value_parser(clap::value_parser!(usize))
+                .required(false)
+            )
+            .arg(arg!(--format <FORMAT> "Output format")
+                .value_parser(["text", "markdown"])
+                .default_value("text")
+            )
+            .arg(arg!(-a --"all-time" "Show the whole history as monthly counts").required(false))
+            .arg(arg!(--"min-streak" <N> "Only show habits with a current streak of at least N days")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+            )
+            .arg(arg!(--page <N> "Only show page N of the habits")
+                .value_parser(clap::value_parser!(usize))
+                .required(false)
+            )
+            .arg(arg!(--"page-size" <N> "Habits per page for --page")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+            )
+            .arg(arg!(--only <NAME> "Only show this habit, can be repeated")
+                .action(ArgAction::Append)
+                .required(false)
+            )
+            .arg(arg!(--exclude <NAME> "Hide this habit, can be repeated")
+                .action(ArgAction::Append)
+                .required(false)
+            )
+            .arg(arg!(--"no-pager" "Never pipe long output through $PAGER"))
+            .arg(arg!(--fill "Show unmarked past days as missed (·)"))
+            .arg(arg!(--weekly "Add a column per week showing whether the weekly goal was met"))
+            .arg(arg!(--"highlight-today" "Accent today's column, needs --color"))
+            .arg(arg!(--header <STYLE> "Day numbers as last digits only, or two-row with tens above units")
+                .value_parser(["single", "two-row"])
+                .default_value("single")
+            )
+            .arg(arg!(--after <DAY> "Only show days from DAY of the month on")
+                .value_parser(clap::value_parser!(i32))
+            )
+            .arg(arg!(--before <DAY> "Only show days up to DAY of the month")
+                .value_parser(clap::value_parser!(i32))
+            )
+            .arg(arg!(date: [DATE]).required(false).help("Optional date in YYYY-MM format, defaults to HTRACKR_DEFAULT_MONTH or the current month"))
         )
         .subcommand(Command::new("create")
             .about("Create new habit")
             .arg(arg!(name: [NAME]))
+            .arg(arg!(--backfill <DAYS> "Also mark the last DAYS days, including today")
+                .required(false)
+                .value_parser(clap::value_parser!(u32))
+            )
+            .arg(arg!(--tag <TAG> "Tag instead of the defaults.tag setting"))
+            .arg(arg!(--"no-tag" "Skip the defaults.tag setting").conflicts_with("tag"))
+            .arg(arg!(--schedule <SCHEDULE> "Schedule instead of the defaults.schedule setting")
+                .value_parser(Schedule::NAMES)
+            )
             .arg_required_else_help(true)
         )
         .subcommand(Command::new("delete")
-            .about("Delete habit")
+            .about("Move habit to the trash")
             .arg(arg!(name: [NAME]))
+            .arg(arg!(--permanent "Delete habit and all entries for good, also if it is in the trash"))
+            .arg(arg!(-f --force "Skip the confirmation of --permanent").requires("permanent"))
             .arg_required_else_help(true)
         )
+        .subcommand(Command::new("restore")
+            .about("Restore habit from the trash")
+            .arg(arg!(name: [NAME]))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("trash")
+            .about("List habits in the trash")
+            .arg(arg!(--purge "Delete every habit in the trash for good"))
+        )
         .subcommand(Command::new("rename")
             .about("Rename habit")
             .arg(arg!(name: [NAME]))
             .arg(arg!(new_name: [NEW_NAME]))
+            .arg(arg!(-v --verbose "Print the habit id, which stays the same"))
+            .arg(arg!(--"auto-suffix" "If the new name is taken, add the first free suffix like (2)"))
+        )
+        .subcommand(Command::new("reorder")
+            .about("Move habit to position in list")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(position: [POSITION]).value_parser(clap::value_parser!(usize)))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("color")
+            .about("Set habit color used by list --color")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(color: [COLOR]).help("Color name, or none to clear"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("interval")
+            .about("Expect habit every N days instead of daily")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(days: [DAYS]).value_parser(clap::value_parser!(u32)))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("schedule")
+            .about("Set which days of the week habit is expected")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(schedule: [SCHEDULE]).value_parser(Schedule::NAMES))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("goal")
+            .about("Set how many days a week habit should be marked")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(days: [DAYS]).help("Days per week, or none to clear"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("pause")
+            .about("Pause habit from today, streaks skip paused days")
+            .arg(arg!(name: [NAME]))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("resume")
+            .about("Resume paused habit from today")
+            .arg(arg!(name: [NAME]))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("tag")
+            .about("Manage habit tags")
+            .arg_required_else_help(true)
+            .subcommand(Command::new("add")
+                .about("Add tag to habit")
+                .arg(arg!(name: <NAME>))
+                .arg(arg!(tag: <TAG>))
+            )
+            .subcommand(Command::new("remove")
+                .about("Remove tag from habit")
+                .arg(arg!(name: <NAME>))
+                .arg(arg!(tag: <TAG>))
+            )
+            .subcommand(Command::new("rename")
+                .about("Rename tag on all habits")
+                .arg(arg!(tag: <TAG>))
+                .arg(arg!(new_tag: <NEW_TAG>))
+            )
+            .subcommand(Command::new("delete")
+                .about("Remove tag from all habits")
+                .arg(arg!(tag: <TAG>))
+            )
+            .subcommand(Command::new("list")
+                .about("List tags of habit, or all tags with their habits")
+                .arg(arg!(name: [NAME]))
+            )
         )
         .subcommand(Command::new("id")
             .arg(arg!(name: [NAME]))
             .about("Get ID")
         )
         .subcommand(Command::new("mark")
-            .about("Mark habit as complete for date")
-            .arg(arg!(name: [NAME]))
+            .about("Mark habits as complete for date")
+            .arg(arg!(args: [ARGS]).num_args(1..).help("Habit names, optionally followed by a date"))
             .arg_required_else_help(true)
-            .arg(arg!(date: [DATE]).required(false).help(date_help))
+            .override_usage("htrackr mark [OPTIONS] <NAME>... [DATE]")
+            .after_help(format!("DATE: {}", date_help))
+            .arg(arg!(--"skip-missing" "Skip habits that don't exist instead of failing"))
+            .arg(arg!(-q --quiet "Don't print how many days were marked"))
+            .arg(arg!(--cron "Print nothing and succeed if already marked, for scheduled runs"))
+            .arg(arg!(--"no-notify" "Don't run the hooks.mark command"))
         )
         .subcommand(Command::new("unmark")
             .about("Unmark habit as complete for date")
             .arg(arg!(name: [NAME]))
             .arg_required_else_help(true)
             .arg(arg!(date: [DATE]).required(false).help(date_help))
+            .arg(arg!(--weekends "Only unmark weekend days of the range"))
+        )
+        .subcommand(Command::new("note")
+            .about("Show or set the note of a marked day")
+            .arg(arg!(name: <NAME>))
+            .arg(arg!(date: <DATE>).help("Date in YYYY-MM-DD format or yesterday (y)"))
+            .arg(arg!(text: [TEXT]).help("New note, the day has to be marked"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("version")
+            .about("Print version of htrackr, SQLite and the database schema")
+        )
+        .subcommand(Command::new("status")
+            .about("Print a one line summary of all habits")
+        )
+        .subcommand(Command::new("diff")
+            .about("Compare which habits were marked on two days")
+            .arg(arg!(first: <DATE1>).help("Date in YYYY-MM-DD format or yesterday (y)"))
+            .arg(arg!(second: <DATE2>).help("Date in YYYY-MM-DD format or yesterday (y)"))
+        )
+        .subcommand(Command::new("top")
+            .about("Show the best habits")
+            .arg(arg!(--by <METRIC> "Rank by current streak, lifetime rate or number of marks")
+                .value_parser(Metric::NAMES)
+                .default_value("streak")
+            )
+            .arg(arg!(--limit <N> "Number of habits to show")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("5")
+            )
+        )
+        .subcommand(Command::new("show")
+            .about("Show habit's month as a calendar with streaks and completion rates")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(--"first-day-of-week" <DAY> "Start weeks on this day, defaults to the week.first_day setting")
+                .value_parser(config::WEEK_STARTS)
+                .required(false)
+            )
+            .arg(arg!(--json "Print everything as JSON"))
+            .arg(arg!(date: [DATE]).required(false).help(short_date_help))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("activity")
+            .about("Show how many habits were marked each day")
+            .arg(arg!(range: [RANGE]).required(false).help("Date range like 2024-03-01..2024-03-31, defaults to the last 14 days"))
+        )
+        .subcommand(Command::new("trend")
+            .about("Show how often habit was marked per week or month")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(--group <GROUP> "Bin size")
+                .value_parser(Group::NAMES)
+                .default_value("monthly")
+            )
+            .arg(arg!(--from <DATE> "Start of range, defaults to when the habit was created").required(false))
+            .arg(arg!(--to <DATE> "End of range, defaults to today").required(false))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("today")
+            .about("Show which habits are done today")
+            .arg(arg!(--summary "Print how many habits are done"))
+            .arg(arg!(--color [WHEN] "Color habits by streak, auto skips it when piped or NO_COLOR is set")
+                .value_parser(ColorChoice::NAMES)
+                .default_value("never")
+                .default_missing_value("auto")
+                .require_equals(true)
+            )
+            .arg(arg!(--"long-streak" <DAYS> "Streaks of at least this many days are green")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("7")
+            )
+        )
+        .subcommand(Command::new("count")
+            .about("Print number of marked days")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(--from <DATE> "Count from this date, inclusive").required(false))
+            .arg(arg!(--to <DATE> "Count up to this date, inclusive").required(false))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("log")
+            .about("List marked days of habit")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(--"plain-dates" "Print only one YYYY-MM-DD date per line"))
+            .arg(arg!(--relative "Add labels like today, yesterday or 3 days ago").conflicts_with("plain-dates"))
+            .arg(arg!(-r --reverse "Newest days first").visible_alias("desc"))
+            .arg(arg!(--limit <N> "Only the N most recent days")
+                .value_parser(clap::value_parser!(usize))
+                .required(false)
+            )
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("stats")
+            .about("Show statistics for habit")
+            .arg(arg!(name: [NAME]))
+            .arg(arg!(--from <DATE> "Start of range, defaults to 30 days ago or HTRACKR_DEFAULT_RANGE").required(false))
+            .arg(arg!(--to <DATE> "End of range, defaults to today").required(false))
+            .arg(arg!(--"weekday-breakdown" "Show how often the habit was marked on each weekday"))
+            .arg(arg!(--"since-created" "Start the range when the habit was created").conflicts_with("from"))
+            .arg(arg!(-a --all "Show statistics for all habits").conflicts_with("name"))
+            .arg(arg!(--missed "List the days in the range that were not marked"))
+            .arg(arg!(--json "Print statistics as JSON"))
+            .arg(arg!(--"summary-only" "Only print totals over all habits").requires("all").conflicts_with("json"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("reset")
+            .about("Delete all habits and entries")
+            .arg(arg!(-y --yes "Skip the confirmation prompt"))
+        )
+        .subcommand(Command::new("config")
+            .about("Show or change settings")
+            .arg(arg!(key: [KEY]).required(false))
+            .arg(arg!(value: [VALUE]).required(false))
+        )
+        .subcommand(Command::new("backup")
+            .about("Copy the database to a file")
+            .arg(arg!(dest: [DEST]).help("Destination file"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("compact")
+            .about("Write a compacted copy of the database to a new file")
+            .arg(arg!(dest: [DEST]).help("Destination file, must not exist"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(Command::new("doctor")
+            .about("Check the database for entries with invalid dates")
+            .arg(arg!(--fix "Delete the invalid entries"))
+        )
+        .subcommand(Command::new("replay")
+            .about("Recompute derived data from the marked days and report inconsistencies")
+            .visible_alias("recompute")
+        )
+        .subcommand(Command::new("batch")
+            .about("Run commands from stdin, one per line, in a single transaction")
+            .arg(arg!(--"continue-on-error" "Report failing commands and keep going instead of rolling back"))
+        )
+        .subcommand(Command::new("export")
+            .about("Export marked days")
+            .arg(arg!(-f --format <FORMAT> "Export format")
+                .value_parser(EXPORT_FORMATS)
+                .default_value("ical")
+            )
+            .arg(arg!(-o --output <FILE> "Write to file instead of stdout").required(false))
+            .arg(arg!(--"no-entries" "Only export habits, without marked days"))
+            .arg(arg!(--since <DATE> "Only export marked days on or after DATE, habits are always exported")
+                .required(false)
+                .conflicts_with("no-entries")
+            )
+            .arg(arg!(--"output-dir" <DIR> "Write to a dated file in this directory")
+                .required(false)
+                .conflicts_with("output")
+            )
+            .arg(arg!(name: [NAME]).required(false).help("Only export this habit, or the png file before the habit"))
+            .arg(arg!(habit: [HABIT]).required(false).help("Habit of a png export written to the NAME file"))
+        )
+        .subcommand(Command::new("import")
+            .about("Import habits and entries from a json or csv export")
+            .arg(arg!(file: [FILE]))
+            .arg(arg!(-f --format <FORMAT> "Import format, csv reads habit,date rows")
+                .value_parser(["json", "csv"])
+                .default_value("json")
+            )
+            .arg(arg!(--"dry-run" "Only report what would change"))
+            .arg_required_else_help(true)
         )
 }
 
-fn list(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+fn list(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
 
-    let list = storage.habit_list()?;
-    // let compact = matches.contains_id("compact");
-    let local = chrono::Local::now();
+    let mut list = storage.habit_list()?;
 
-    let year;
-    let month;
+    if let Some(only) = matches.get_many::<String>("only") {
+        list = only_habits(storage, list, &only.cloned().collect::<Vec<String>>())?;
+    }
 
-    if let Some(date) = matches.get_one::<String>("date") {
-        let mut full_date = date.clone();
-        full_date.push_str("-01");
-        let date = Date::from_string(&full_date)?;
-        year = date.year;
-        month = date.month;
-    } else {
-        year = local.year();
-        month = local.month() as i32;
+    if let Some(excluded) = matches.get_many::<String>("exclude") {
+        list = exclude_habits(list, &excluded.cloned().collect::<Vec<String>>());
     }
-    
 
-    let num_days = date::num_days(year, month);
+    if let Some(min_streak) = matches.get_one::<u32>("min-streak") {
+        list = filter_min_streak(list, storage, &clock.today(), *min_streak)?;
+    }
 
-    let date_start = Date {
-        year: year,
-        month: month,
-        day: 01,
-    };
-    let date_end = Date {
-        year: year,
-        month: month,
-        day: num_days,
-    };
-
-    let month_display = format!("{:04}-{:02}", year, month);
-
-    let mut target_indent = month_display.len() + 2;
-    for name in &list {
-        let len = name.len();
-        if len > target_indent {
-            target_indent = len;
-        }
-    }
-
-    let mut line0 = String::new();
-    line0.push_str(&month_display);
-    line0.push_str(&str::repeat(" ", target_indent - month_display.len()));
-    line0.push_str("| ");
-    for i in 1..num_days+1 {
-        line0.push_str(&format!("{}", i % 10));
-    }
-    println!("{}", line0);
-
-    for name in &list {
-        let days = storage.get_marked_days(&name, &date_start, &date_end);
-        match days {
-            Ok(days) =>{
-                let indent_count = target_indent - name.len();
-                let indent = str::repeat(" ", indent_count);
-                
-                let cap = name.len() + indent.len() + 1 + num_days as usize + 1;
-                let mut line = String::with_capacity(cap);
-                line.push_str(&name);
-                line.push_str(&indent);
-                line.push_str("| ");
-
-                for i in 1..num_days+1 {
-                    match days.iter().any(|f| f.day == i) {
-                        true => line.push_str("X"),
-                        false => line.push_str(" "),
-                    }
-                }
+    if let (Some(page), Some(page_size)) = (matches.get_one::<usize>("page"), matches.get_one::<usize>("page-size")) {
+        let range = pager::page_range(list.len(), *page, *page_size)?;
+        list = list[range].to_vec();
+    }
 
-                // if cap != line.len() {
-                //     panic!("capacity")
-                // }
-                
-                println!("{}", line);
-            },
-            Err(e) => println!("error {}", e),
-        };
+    if matches.get_flag("all-time") {
+        return list_all_time(&list, storage);
+    }
+
+    // let compact = matches.contains_id("compact");
+    let choice = ColorChoice::parse(matches.get_one::<String>("color").map_or("never", |c| c.as_str()))?;
+    let use_color = choice.use_color(env::var("NO_COLOR").ok().as_deref(), stdout().is_terminal());
+
+    let date = matches.get_one::<String>("date");
+    let (year, month) = match date {
+        Some(_) => month_arg(date, clock)?,
+        None => match env_default_month(clock)? {
+            Some(default) => default,
+            None => month_arg(None, clock)?,
+        },
+    };
+
+    let window = day_window(matches.get_one::<i32>("after").copied(), matches.get_one::<i32>("before").copied(), year, month)?;
+    let (date_start, date_end) = match window {
+        Some((first, last)) => (Date { year, month, day: first }, Date { year, month, day: last }),
+        None => (Date::first_of_month(year, month)?, Date::last_of_month(year, month)?),
+    };
+
+    let mut days_by_habit = storage.marked_days_in_range(&date_start, &date_end)?;
+    let mut colors = match use_color {
+        true => storage.colors()?,
+        false => HashMap::new(),
+    };
+    let paused_habits = storage.paused_habits(&clock.today())?;
+    let weekly_goals = storage.weekly_goals()?;
+
+    let mut rows = Vec::with_capacity(list.len());
+    for name in list {
+        let days = days_by_habit.remove(&name).unwrap_or_default();
+        let marked = DateRange::new(&date_start, &date_end)
+            .map(|day| days.contains(&day))
+            .collect();
+
+        let color = colors.remove(&name);
+        let paused = paused_habits.contains(&name);
+        let weekly_goal = weekly_goals.get(&name).copied();
+
+        rows.push(GridRow { name, color, marked, paused, weekly_goal });
+    }
+
+    let name_width = matches.get_one::<usize>("name-width").copied();
+    let fill = match matches.get_flag("fill") {
+        true => Some(clock.today()),
+        false => None,
+    };
+    let weekly = matches.get_flag("weekly");
+    let highlight = match matches.get_flag("highlight-today") {
+        true => Some(clock.today()),
+        false => None,
+    };
+    let two_row_header = matches.get_one::<String>("header").is_some_and(|h| h == "two-row");
+    let palette = match use_color {
+        true => palette(storage)?,
+        false => Palette::default(),
+    };
+    let grid = MonthGrid { year, month, rows, name_width, fill, weekly, highlight, two_row_header, palette, window };
+
+    if matches.get_flag("group-by-tag") {
+        let groups = storage.habits_by_tag()?
+            .into_iter()
+            .map(|(tag, names)| (tag.unwrap_or("(untagged)".to_owned()), names))
+            .collect::<Vec<_>>();
+        pager::print(&grid.render_text_grouped(&groups, use_color), !matches.get_flag("no-pager"));
+        return Ok(());
     }
 
+    let output = match matches.get_one::<String>("format").map(|f| f.as_str()) {
+        _ if matches.get_flag("compact-numbers") => grid.render_numbers(),
+        Some("markdown") => grid.render_markdown(),
+        _ => grid.render_text(use_color),
+    };
+    pager::print(&output, !matches.get_flag("no-pager"));
+
     Ok(())
 }
 
-fn create(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+/// Year and month of a `YYYY-MM` argument, the current month if not given.
+fn month_arg(date: Option<&String>, clock: &dyn Clock) -> Result<(i32, i32), CliError> {
 
-    if let Some(name) = matches.get_one::<String>("name") {
-        storage.create_habit(name)?;
-    } else {
-        return Err(CliError::new("name is required"));
+    match date {
+        Some(date) => {
+            let (year, month) = Date::parse_month(date)?;
+            // months that are partly inside the bounds are fine
+            let last = Date::last_of_month(year, month)?;
+            match last < clock.min_date() {
+                true => check_date_bounds(last, clock)?,
+                false => check_date_bounds(Date::first_of_month(year, month)?.max(clock.min_date()), clock)?,
+            };
+            Ok((year, month))
+        },
+        None => {
+            let today = clock.today();
+            Ok((today.year, today.month))
+        },
     }
+}
 
-    Ok(())
+/// Month `list` shows without a date, from `HTRACKR_DEFAULT_MONTH`.
+fn env_default_month(clock: &dyn Clock) -> Result<Option<(i32, i32)>, CliError> {
+
+    match env::var("HTRACKR_DEFAULT_MONTH") {
+        Ok(value) if !value.is_empty() => Ok(Some(parse_default_month(&value, clock)?)),
+        _ => Ok(None),
+    }
 }
 
-fn delete(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+/// Month from a value like `2024-05`, `current`, `previous` or `previous-until-5`,
+/// which is the previous month up to the 5th and the current month after.
+fn parse_default_month(value: &str, clock: &dyn Clock) -> Result<(i32, i32), CliError> {
 
-    
-    if let Some(name) = matches.get_one::<String>("name") {
-        println!("Delete habit {} and all entires? y/n", name);
-        let mut line = String::with_capacity(1);
-        match stdin().read_line(&mut line) {
-            Ok(_) => {
-                if line.len() > 1 && line.as_bytes()[0] == b'y' {
-                    storage.delete_habit(name)?;
-                }
+    let today = clock.today();
+    let previous = match today.month {
+        1 => (today.year - 1, 12),
+        month => (today.year, month - 1),
+    };
+
+    match value {
+        "current" => Ok((today.year, today.month)),
+        "previous" => Ok(previous),
+        _ if value.starts_with(|c: char| c.is_ascii_digit()) => month_arg(Some(&value.to_owned()), clock),
+        _ => match value.strip_prefix("previous-until-").map(|day| day.parse::<i32>()) {
+            Some(Ok(day)) if (1..=31).contains(&day) => match today.day <= day {
+                true => Ok(previous),
+                false => Ok((today.year, today.month)),
             },
-            Err(err) => return Err(CliError(err.to_string())),
-        }
-        return  Ok(());
+            _ => Err(CliError(format!("invalid HTRACKR_DEFAULT_MONTH {}, expected YYYY-MM, current, previous or previous-until-DAY", value))),
+        },
     }
-
-    return Err(CliError::new("invalid args"));
 }
 
-fn rename(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+fn show(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock, locale: Locale) -> Result<(), CliError> {
 
     if let Some(name) = matches.get_one::<String>("name") {
-        if let Some(new_name) = matches.get_one::<String>("new_name") {
-            storage.rename_habit(&name, &new_name)?;
+        let (year, month) = month_arg(matches.get_one::<String>("date"), clock)?;
 
+        let week_start = match matches.get_one::<String>("first-day-of-week") {
+            Some(day) => config::parse_week_start(day)?,
+            None => storage.load_config()?.week_start,
+        };
+
+        let overview = Overview::compute(storage, name, year, month, &clock.today())?;
+
+        if matches.get_flag("json") {
+            println!("{}", serde_json::to_string(&overview.json_fields())?);
             return Ok(());
         }
+
+        let start = Date::first_of_month(year, month)?;
+        let end = Date::last_of_month(year, month)?;
+        let marked = DateRange::new(&start, &end)
+            .map(|day| overview.marked.contains(&day))
+            .collect();
+
+        print!("{}", Calendar { year, month, marked, week_start }.render(locale));
+        println!();
+        println!("schedule: {}", overview.schedule.name());
+        println!("current streak: {}", overview.stats.current_streak);
+        println!("longest streak: {}", overview.stats.longest_streak);
+        println!("this month: {}/{} days ({:.0}%)", overview.stats.marked, overview.stats.total, overview.stats.rate() * 100.0);
+        println!("all time: {:.0}%", overview.all_time_rate * 100.0);
+        match &overview.last_marked {
+            Some(day) => println!("last marked: {}", day.to_string()?),
+            None => println!("last marked: never"),
+        }
+        return Ok(());
     }
 
-    return Err(CliError::new("invalid args"));
+    Err(CliError::new("invalid args"))
 }
 
-fn id(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+fn trend(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
 
     if let Some(name) = matches.get_one::<String>("name") {
-        let id = storage.get_habit_id(&name)?;
-        println!("{}", id);
+        let today = clock.today();
+        let group = Group::parse(matches.get_one::<String>("group").map_or("monthly", |g| g.as_str()))?;
+
+        let from = match matches.get_one::<String>("from") {
+            Some(date) => parse_date_arg(date, clock)?,
+            None => created_or_first_marked(storage, name, &today)?,
+        };
+        let to = match matches.get_one::<String>("to") {
+            Some(date) => parse_date_arg(date, clock)?,
+            None => today,
+        };
+
+        let marked = storage.marked_set(name, &from, &to)?;
+        for bin in stats::bin_counts(&marked, &from, &to, group)? {
+            let line = format!("{} {:>2}/{:<2} {}", bin.start.to_string()?, bin.marked, bin.days, "█".repeat(bin.marked as usize));
+            println!("{}", line.trim_end());
+        }
         return Ok(());
     }
 
-    return Err(CliError::new("invalid args"));
+    Err(CliError::new("invalid args"))
 }
 
-fn parse_date_arg(date: &str) -> Result<Date, CliError> {
-    if date == "yesterday" || date == "y" {
-        return Ok(Date::yesterday());
+fn activity(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let today = clock.today();
+    let (start, end) = match matches.get_one::<String>("range") {
+        Some(range) => match parse_date_range(range, clock)? {
+            Some(range) => range,
+            None => return Err(CliError(format!("invalid range {}, expected START..END", range))),
+        },
+        None => (today.sub_days(13), today),
+    };
+
+    print!("{}", format_activity(&storage.daily_totals(&start, &end)?)?);
+
+    Ok(())
+}
+
+/// One line per day with a bar as long as the number of marked habits.
+fn format_activity(totals: &[(Date, u32)]) -> Result<String, CliError> {
+
+    let mut result = String::new();
+    for (day, count) in totals {
+        let line = format!("{} {:>3} {}", day.to_string()?, count, "█".repeat(*count as usize));
+        result.push_str(line.trim_end());
+        result.push('\n');
     }
-    
-    Date::from_string(date)
+
+    Ok(result)
+}
+
+/// Habits of `list` named in `only`, in list order. Fails on unknown names.
+fn only_habits(storage: &Storage, list: Vec<String>, only: &[String]) -> Result<Vec<String>, CliError> {
+
+    for name in only {
+        storage.resolve_habit(name)?;
+    }
+
+    Ok(list.into_iter().filter(|name| only.contains(name)).collect())
+}
+
+fn exclude_habits(list: Vec<String>, excluded: &[String]) -> Vec<String> {
+    list.into_iter().filter(|name| !excluded.contains(name)).collect()
+}
+
+fn filter_min_streak(list: Vec<String>, storage: &Storage, today: &Date, min_streak: u32) -> Result<Vec<String>, CliError> {
+
+    if min_streak == 0 {
+        return Ok(list);
+    }
+
+    let mut result = Vec::with_capacity(list.len());
+    for name in list {
+        if storage.current_streak(&name, today)? >= min_streak {
+            result.push(name);
+        }
+    }
+
+    Ok(result)
+}
+
+fn list_all_time(list: &[String], storage: &Storage) -> Result<(), CliError> {
+
+    let mut target_indent = 0;
+    for name in list {
+        if name.len() > target_indent {
+            target_indent = name.len();
+        }
+    }
+
+    for name in list {
+        let indent = str::repeat(" ", target_indent - name.len());
+
+        let first = storage.first_marked(name)?;
+        let last = storage.last_marked(name)?;
+
+        let (first, last) = match (first, last) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                println!("{}{} | no entries", name, indent);
+                continue;
+            }
+        };
+
+        let month_counts = month_counts(storage, name, &first, &last, 1000)?;
+        println!("{}{} | {} - {} | {}", name, indent, first.to_string()?, last.to_string()?, sparkline(&month_counts));
+    }
+
+    Ok(())
+}
+
+/// Marks per month from the month of `first` to the month of `last`, read a page
+/// at a time so decades of entries are never in memory at once.
+fn month_counts(storage: &Storage, name: &str, first: &Date, last: &Date, page_size: usize) -> Result<Vec<u32>, CliError> {
+
+    let mut counts = vec![0; date::month_span(first, last).len()];
+
+    let total = storage.count_entries(name, Some(first), Some(last))? as usize;
+    for offset in (0..total).step_by(page_size) {
+        for day in storage.get_marked_days_paged(name, first, last, offset, page_size)? {
+            counts[((day.year - first.year) * 12 + day.month - first.month) as usize] += 1;
+        }
+    }
+
+    Ok(counts)
+}
+
+fn sparkline(counts: &[u32]) -> String {
+
+    let bars = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = counts.iter().copied().max().unwrap_or(0);
+
+    let mut line = String::with_capacity(counts.len());
+    for &count in counts {
+        if count == 0 || max == 0 {
+            line.push(bars[0]);
+            continue;
+        }
+        // any non-zero month gets at least the lowest bar
+        let index = ((count as usize * (bars.len() - 1)).div_ceil(max as usize)).max(1);
+        line.push(bars[index]);
+    }
+
+    line
 }
 
-fn mark(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+fn create(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
 
     if let Some(name) = matches.get_one::<String>("name") {
-        if let Some(date) = matches.get_one::<String>("date") {
-            storage.mark_habit(&name, &parse_date_arg(&date)?)?;
-            return Ok(());
-        } else {
-            let today = Date::today();
-            storage.mark_habit(&name, &today)?;
-            return Ok(());
+        let mut defaults = storage.load_config()?.habit_defaults;
+        if let Some(tag) = matches.get_one::<String>("tag") {
+            defaults.tag = Some(tag.clone());
+        }
+        if matches.get_flag("no-tag") {
+            defaults.tag = None;
         }
+        if let Some(schedule) = matches.get_one::<String>("schedule") {
+            defaults.schedule = Schedule::parse(schedule)?;
+        }
+
+        storage.with_transaction(|s| {
+            match matches.get_one::<u32>("backfill") {
+                Some(days) => create_with_backfill(s, name, *days, &clock.today())?,
+                None => {
+                    s.create_habit(name)?;
+                    s.set_created_at(name, &clock.today())?;
+                },
+            }
+            s.apply_habit_defaults(name, &defaults)
+        })?;
+    } else {
+        return Err(CliError::new("name is required"));
+    }
+
+    Ok(())
+}
+
+/// Creates a habit and marks the `days` days up to and including `today`.
+fn create_with_backfill(storage: &Storage, name: &str, days: u32, today: &Date) -> Result<(), CliError> {
+
+    if !(1..=366).contains(&days) {
+        return Err(CliError(format!("invalid backfill {}, expected 1 to 366 days", days)));
     }
 
-    return Err(CliError::new("invalid args"));
+    let start = today.sub_days(days as i64 - 1);
+    let entries = DateRange::new(&start, today)
+        .map(|day| (name.to_owned(), day))
+        .collect::<Vec<_>>();
+
+    storage.with_transaction(|s| {
+        s.create_habit(name)?;
+        s.set_created_at(name, &start)?;
+        s.batch_mark(&entries)?;
+        Ok(())
+    })
 }
 
-fn unmark(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+fn delete(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
 
     if let Some(name) = matches.get_one::<String>("name") {
-        if let Some(date) = matches.get_one::<String>("date") {
-            storage.unmark_habit(&name, &parse_date_arg(&date)?)?;
+        if !matches.get_flag("permanent") {
+            storage.soft_delete(name, &clock.today())?;
+            println!("Moved habit {} to the trash, use restore to bring it back", name);
             return Ok(());
-        } else {
-            let today = Date::today();
-            storage.unmark_habit(&name, &today)?;
+        }
+
+        if matches.get_flag("force") {
+            storage.delete_habit(name)?;
+            println!("Deleted habit {}", name);
             return Ok(());
         }
 
-    }
+        abort_on_interrupt();
+        match confirm_delete(storage, name, &mut stdin().lock())? {
+            Some(true) => println!("Deleted habit {}", name),
+            Some(false) => (),
+            None => {
+                println!("Aborted");
+                process::exit(130);
+            },
+        }
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn restore(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        storage.restore(name)?;
+        println!("Restored habit {}", name);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn trash(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if matches.get_flag("purge") {
+        println!("Deleted {} habits from the trash", storage.purge_trash()?);
+        return Ok(());
+    }
+
+    for (name, deleted_at) in storage.list_trashed()? {
+        println!("{} (deleted {})", name, deleted_at.to_string()?);
+    }
+
+    Ok(())
+}
+
+/// Asks before deleting `name`, returns whether it was deleted or `None` if the
+/// input ended before an answer was given.
+fn confirm_delete(storage: &Storage, name: &str, input: &mut dyn BufRead) -> Result<Option<bool>, CliError> {
+
+    println!("Delete habit {} and all entries? y/N", name);
+
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) => return Ok(None),
+        Ok(_) => (),
+        Err(err) => return Err(CliError(err.to_string())),
+    }
+
+    if !parse_yes_no(&line) {
+        return Ok(Some(false));
+    }
+
+    storage.delete_habit(name)?;
+    Ok(Some(true))
+}
+
+/// Makes Ctrl-C at a prompt exit with status 130 before anything was changed.
+#[cfg(unix)]
+fn abort_on_interrupt() {
+
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        let message = b"\nAborted\n";
+        // only async-signal-safe calls in here
+        unsafe {
+            libc::write(libc::STDOUT_FILENO, message.as_ptr() as *const libc::c_void, message.len());
+            libc::_exit(130);
+        }
+    }
+
+    unsafe {
+        libc::signal(libc::SIGINT, on_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn abort_on_interrupt() {}
+
+/// Interprets a confirmation answer, anything other than y/yes counts as no.
+fn parse_yes_no(line: &str) -> bool {
+
+    let answer = line.trim().to_lowercase();
+    answer == "y" || answer == "yes"
+}
+
+fn reset(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if !matches.get_flag("yes") {
+        println!("This deletes ALL habits and entries. Type RESET to continue:");
+        let mut line = String::new();
+        if let Err(err) = stdin().read_line(&mut line) {
+            return Err(CliError(err.to_string()));
+        }
+        if line.trim() != "RESET" {
+            println!("Aborted");
+            return Ok(());
+        }
+    }
+
+    storage.clear_all()?;
+    println!("Deleted all habits and entries");
+
+    Ok(())
+}
+
+fn config(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    match (matches.get_one::<String>("key"), matches.get_one::<String>("value")) {
+        (Some(key), Some(value)) => {
+            storage.set_setting(key, value)?;
+            if key.starts_with("palette.") {
+                palette(storage)?;
+            }
+        },
+        (Some(key), None) => println!("{}", storage.load_config()?.get(key)?),
+        _ => {
+            for (key, value) in storage.settings()? {
+                println!("{} = {}", key, value);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Days of the month `list` shows from `--after` and `--before`, both inclusive.
+/// `None` when neither is given.
+fn day_window(after: Option<i32>, before: Option<i32>, year: i32, month: i32) -> Result<Option<(i32, i32)>, CliError> {
+
+    if after.is_none() && before.is_none() {
+        return Ok(None);
+    }
+
+    let num_days = date::num_days(year, month);
+    let (first, last) = (after.unwrap_or(1), before.unwrap_or(num_days));
+    for day in [first, last] {
+        if !(1..=num_days).contains(&day) {
+            return Err(CliError(format!("invalid day {}, {:04}-{:02} has {} days", day, year, month, num_days)));
+        }
+    }
+    if first > last {
+        return Err(CliError(format!("--after {} is later than --before {}", first, last)));
+    }
+
+    Ok(Some((first, last)))
+}
+
+/// Grid colors from the `palette.*` settings, warning about unknown color names.
+fn palette(storage: &Storage) -> Result<Palette, CliError> {
+
+    let (palette, warnings) = Palette::parse(&storage.load_config()?.palette);
+    for warning in warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    Ok(palette)
+}
+
+fn backup(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(dest) = matches.get_one::<String>("dest") {
+        storage.backup_to(dest)?;
+
+        println!("Backed up to {} ({} bytes)", dest, file_size(dest)?);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn compact(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(dest) = matches.get_one::<String>("dest") {
+        storage.vacuum_into(dest)?;
+
+        println!("Compacted to {} ({} bytes)", dest, file_size(dest)?);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn doctor(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    let invalid = storage.find_invalid_dates()?;
+    if invalid.is_empty() {
+        println!("No problems found");
+        return Ok(());
+    }
+
+    for (name, date) in &invalid {
+        println!("{}: invalid date {}", name, date);
+    }
+
+    if matches.get_flag("fix") {
+        println!("Removed {} entries", storage.remove_invalid_dates()?);
+    } else {
+        println!("Run with --fix to remove {} entries", invalid.len());
+    }
+
+    Ok(())
+}
+
+fn replay(storage: &Storage) -> Result<(), CliError> {
+
+    let summary = storage.recompute_derived()?;
+    for anomaly in &summary.anomalies {
+        println!("{}", anomaly);
+    }
+
+    match summary.anomalies.len() {
+        0 => println!("Replayed {} entries, no problems found", summary.entries),
+        n => println!("Replayed {} entries, found {} problems", summary.entries, n),
+    }
+
+    Ok(())
+}
+
+fn file_size(path: &str) -> Result<u64, CliError> {
+
+    match fs::metadata(path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(err) => Err(CliError(format!("failed to read {}: {}", path, err))),
+    }
+}
+
+fn rename(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        if let Some(new_name) = matches.get_one::<String>("new_name") {
+            let new_name = match matches.get_flag("auto-suffix") {
+                true => storage.rename_habit_auto_suffix(name, new_name)?,
+                false => {
+                    storage.rename_habit(name, new_name)?;
+                    new_name.clone()
+                },
+            };
+
+            if matches.get_flag("verbose") {
+                println!("Renamed habit {} to {} ({})", name, new_name, storage.resolve_habit(&new_name)?);
+            }
+            return Ok(());
+        }
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn id(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        let id = storage.resolve_habit(name)?;
+        println!("{}", id);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn reorder(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        if let Some(position) = matches.get_one::<usize>("position") {
+            storage.set_position(name, *position)?;
+            return Ok(());
+        }
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn set_color(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        if let Some(color) = matches.get_one::<String>("color") {
+            match color.as_str() {
+                "none" => storage.set_color(name, None)?,
+                c => storage.set_color(name, Some(c))?,
+            }
+            return Ok(());
+        }
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn set_interval(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        match matches.get_one::<u32>("days") {
+            Some(days) => storage.set_interval(name, *days)?,
+            None => println!("{}", storage.get_interval(name)?),
+        }
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn set_schedule(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        match matches.get_one::<String>("schedule") {
+            Some(schedule) => storage.set_schedule(name, Schedule::parse(schedule)?)?,
+            None => println!("{}", storage.get_schedule(name)?.name()),
+        }
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn set_goal(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        match matches.get_one::<String>("days").map(|d| d.as_str()) {
+            Some("none") => storage.set_weekly_goal(name, None)?,
+            Some(days) => storage.set_weekly_goal(name, Some(days.parse::<u32>()?))?,
+            None => match storage.get_weekly_goal(name)? {
+                Some(goal) => println!("{} days a week", goal),
+                None => println!("no goal"),
+            },
+        }
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn pause(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        return storage.pause(name, &clock.today());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn resume(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        return storage.resume(name, &clock.today());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn tag(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    match matches.subcommand() {
+        Some(("add", s)) => {
+            if let (Some(name), Some(tag)) = (s.get_one::<String>("name"), s.get_one::<String>("tag")) {
+                return storage.add_tag(name, tag);
+            }
+        },
+        Some(("remove", s)) => {
+            if let (Some(name), Some(tag)) = (s.get_one::<String>("name"), s.get_one::<String>("tag")) {
+                return storage.remove_tag(name, tag);
+            }
+        },
+        Some(("rename", s)) => {
+            if let (Some(tag), Some(new_tag)) = (s.get_one::<String>("tag"), s.get_one::<String>("new_tag")) {
+                let affected = storage.rename_tag(tag, new_tag)?;
+                println!("renamed tag {} to {} on {} habit(s)", tag, new_tag, affected);
+                return Ok(());
+            }
+        },
+        Some(("delete", s)) => {
+            if let Some(tag) = s.get_one::<String>("tag") {
+                let affected = storage.delete_tag(tag)?;
+                println!("removed tag {} from {} habit(s)", tag, affected);
+                return Ok(());
+            }
+        },
+        Some(("list", s)) => {
+            if let Some(name) = s.get_one::<String>("name") {
+                for tag in storage.get_tags(name)? {
+                    println!("{}", tag);
+                }
+                return Ok(());
+            }
+
+            for (tag, names) in storage.habits_by_tag()? {
+                if let Some(tag) = tag {
+                    println!("{}: {}", tag, names.join(", "));
+                }
+            }
+            return Ok(());
+        },
+        _ => (),
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn parse_date_arg(date: &str, clock: &dyn Clock) -> Result<Date, CliError> {
+    if date == "today" {
+        return Ok(clock.today());
+    }
+    if date == "yesterday" || date == "y" {
+        return Ok(clock.yesterday());
+    }
+    
+    check_date_bounds(Date::from_string(date)?, clock)
+}
+
+/// Whether an argument is meant as a date or range, even if it doesn't parse.
+fn looks_like_date(arg: &str) -> bool {
+
+    arg.split("..").all(|part| {
+        matches!(part, "today" | "yesterday" | "y")
+            || (part.len() > 4 && part.as_bytes()[..4].iter().all(|b| b.is_ascii_digit()) && part.as_bytes()[4] == b'-')
+    })
+}
+
+/// Rejects user supplied dates that are almost certainly typos, outside of
+/// `--min-date` and `--max-date`. Imports and stored entries are not checked.
+fn check_date_bounds(date: Date, clock: &dyn Clock) -> Result<Date, CliError> {
+
+    let (min, max) = (clock.min_date(), clock.max_date());
+
+    if date < min || date > max {
+        return Err(CliError(format!("date {} out of range, expected a date from {} to {}", date.to_string()?, min.to_string()?, max.to_string()?)));
+    }
+
+    Ok(date)
+}
+
+fn parse_date_range(range: &str, clock: &dyn Clock) -> Result<Option<(Date, Date)>, CliError> {
+
+    match range.split_once("..") {
+        Some((start, end)) => Ok(Some((parse_date_arg(start, clock)?, parse_date_arg(end, clock)?))),
+        None => Ok(None),
+    }
+}
+
+fn parse_dates(arg: &str, clock: &dyn Clock) -> Result<Vec<Date>, CliError> {
+
+    match parse_date_range(arg, clock)? {
+        Some((start, end)) => date::days_between(&start, &end),
+        None => Ok(vec![parse_date_arg(arg, clock)?]),
+    }
+}
+
+fn mark(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let mut names = match matches.get_many::<String>("args") {
+        Some(args) => args.cloned().collect::<Vec<String>>(),
+        None => return Err(CliError::new("invalid args")),
+    };
+
+    // a trailing argument that looks like a date is the date, not a habit
+    let mut dates = vec![clock.today()];
+    if names.len() > 1 && looks_like_date(&names[names.len() - 1]) {
+        dates = parse_dates(&names[names.len() - 1], clock)?;
+        names.pop();
+    }
+
+    let cron = matches.get_flag("cron");
+    let (summary, skipped) = mark_habits(storage, &names, &dates, matches.get_flag("skip-missing"), cron)?;
+    if !skipped.is_empty() {
+        eprintln!("Skipped missing habits: {}", skipped.join(", "));
+    }
+
+    if let Some(message) = mark_message(&summary, matches.get_flag("quiet") || cron) {
+        println!("{}", message);
+    }
+
+    if let (Some(hook), false) = (storage.load_config()?.mark_hook, matches.get_flag("no-notify")) {
+        // only for new entries, and inside a batch once everything is committed
+        for (name, date) in summary.marked {
+            let hook = hook.clone();
+            storage.after_commit(Box::new(move || run_hook(&hook, &name, &date)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Starts the `hooks.mark` command without waiting for it. Failures are only
+/// reported, the mark itself already succeeded.
+fn run_hook(hook: &str, habit: &str, date: &Date) {
+
+    let started = hook_args(hook, habit, date).and_then(|args| {
+        let (program, args) = match args.split_first() {
+            Some(split) => split,
+            None => return Err(CliError::new("empty command")),
+        };
+        match process::Command::new(program).args(args).stdin(process::Stdio::null()).spawn() {
+            Ok(_) => Ok(()),
+            Err(err) => Err(CliError(format!("failed to run {}: {}", program, err))),
+        }
+    });
+
+    if let Err(err) = started {
+        eprintln!("hooks.mark: {}", err);
+    }
+}
+
+/// Splits the hook like a batch line and fills `{habit}` and `{date}` into each
+/// argument, so names with spaces stay one argument.
+fn hook_args(hook: &str, habit: &str, date: &Date) -> Result<Vec<String>, CliError> {
+
+    let date = date.to_string()?;
+    Ok(split_command_line(hook)?
+        .into_iter()
+        .map(|arg| arg.replace("{habit}", habit).replace("{date}", &date))
+        .collect())
+}
+
+fn mark_message(summary: &BatchSummary, quiet: bool) -> Option<String> {
+
+    match quiet {
+        true => None,
+        false => Some(format_mark_summary(summary)),
+    }
+}
+
+/// Summary line like `marked 3, skipped 1 (already marked)`.
+fn format_mark_summary(summary: &BatchSummary) -> String {
+
+    match summary.skipped {
+        0 => format!("marked {}", summary.inserted),
+        skipped => format!("marked {}, skipped {} (already marked)", summary.inserted, skipped),
+    }
+}
+
+/// Marks every habit for every date. Returns the entries that were added and
+/// the names that were skipped because they don't exist, which is only allowed
+/// with `skip_missing`. A single day that is already marked is an error unless
+/// `idempotent`, more days are always skipped.
+fn mark_habits(storage: &Storage, names: &[String], dates: &[Date], skip_missing: bool, idempotent: bool) -> Result<(BatchSummary, Vec<String>), CliError> {
+
+    let mut existing = vec![];
+    let mut skipped = vec![];
+    for name in names {
+        if storage.habit_exists(name)? {
+            existing.push(name);
+        } else if skip_missing {
+            skipped.push(name.clone());
+        } else {
+            // fails with the not found error and a suggestion
+            storage.resolve_habit(name)?;
+        }
+    }
+
+    if existing.len() == 1 && dates.len() == 1 {
+        let inserted = match idempotent {
+            true => storage.mark_habit_idempotent(existing[0], &dates[0])?,
+            false => storage.mark_habit(existing[0], &dates[0])?,
+        };
+        let summary = match inserted {
+            true => BatchSummary { inserted: 1, skipped: 0, marked: vec![(existing[0].clone(), dates[0].clone())] },
+            false => BatchSummary { inserted: 0, skipped: 1, marked: vec![] },
+        };
+        return Ok((summary, skipped));
+    }
+
+    let mut entries = Vec::with_capacity(existing.len() * dates.len());
+    for name in existing {
+        for date in dates {
+            entries.push((name.clone(), date.clone()));
+        }
+    }
+    let summary = storage.batch_mark(&entries)?;
+
+    Ok((summary, skipped))
+}
+
+fn unmark(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        if let Some(date) = matches.get_one::<String>("date") {
+            if let Some((start, end)) = parse_date_range(date, clock)? {
+                let filter = match matches.get_flag("weekends") {
+                    true => DayFilter::Weekends,
+                    false => DayFilter::All,
+                };
+                let removed = storage.unmark_range(name, &start, &end, filter)?;
+                println!("Unmarked {} days", removed);
+                return Ok(());
+            }
+
+            storage.unmark_habit(name, &parse_date_arg(date, clock)?)?;
+            return Ok(());
+        } else {
+            let today = clock.today();
+            storage.unmark_habit(name, &today)?;
+            return Ok(());
+        }
+
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn count(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        let from = match matches.get_one::<String>("from") {
+            Some(date) => Some(parse_date_arg(date, clock)?),
+            None => None,
+        };
+        let to = match matches.get_one::<String>("to") {
+            Some(date) => Some(parse_date_arg(date, clock)?),
+            None => None,
+        };
+        let (from, to) = match (from, to, env_default_range(&clock.today())?) {
+            (None, None, Some((start, end))) => (Some(start), Some(end)),
+            (from, to, _) => (from, to),
+        };
+
+        let count = storage.count_entries(name, from.as_ref(), to.as_ref())?;
+        println!("{}", count);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn import(matches: &ArgMatches, storage: &Storage) -> Result<(), CliError> {
+
+    if let Some(file) = matches.get_one::<String>("file") {
+        let text = match fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(err) => return Err(CliError(format!("failed to read {}: {}", file, err))),
+        };
+        let dry_run = matches.get_flag("dry-run");
+        let summary = match matches.get_one::<String>("format").map(|f| f.as_str()) {
+            Some("csv") => storage.import_entries(&export::from_csv(&text)?, dry_run)?,
+            _ => storage.import(&export::from_json(&text)?, dry_run)?,
+        };
+
+        if dry_run {
+            println!("Dry run, nothing was changed");
+        }
+        println!("habits: {} created, {} skipped", summary.habits_created, summary.habits_skipped);
+        println!("entries: {} created, {} skipped", summary.entries_created, summary.entries_skipped);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn log(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock, locale: Locale) -> Result<(), CliError> {
+
+    if let Some(name) = matches.get_one::<String>("name") {
+        let range = env_default_range(&clock.today())?;
+        let days = storage.all_entries(Some(name), None)?
+            .into_iter()
+            .map(|entry| entry.date)
+            .filter(|day| range.as_ref().is_none_or(|(start, end)| start <= day && day <= end))
+            .collect::<Vec<Date>>();
+        let days = order_log(days, matches.get_flag("reverse"), matches.get_one::<usize>("limit").copied());
+
+        let today = match matches.get_flag("relative") {
+            true => Some(clock.today()),
+            false => None,
+        };
+
+        print!("{}", format_log(&days, matches.get_flag("plain-dates"), today.as_ref(), locale)?);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+/// Sorts `days` oldest first, or newest first with `reverse`, keeping only
+/// the `limit` most recent.
+fn order_log(mut days: Vec<Date>, reverse: bool, limit: Option<usize>) -> Vec<Date> {
+
+    days.sort();
+    if let Some(limit) = limit {
+        days.drain(..days.len().saturating_sub(limit));
+    }
+    if reverse {
+        days.reverse();
+    }
+
+    days
+}
+
+/// One date per line with its weekday, and a label relative to `today` if given.
+fn format_log(days: &[Date], plain: bool, today: Option<&Date>, locale: Locale) -> Result<String, CliError> {
+
+    let mut result = String::new();
+    for day in days {
+        result.push_str(&day.to_string()?);
+        if !plain {
+            result.push_str(&format!(" {}", locale.weekday(day.day_of_week()?)));
+        }
+        if let Some(today) = today {
+            result.push_str(&format!(" ({})", relative_label(day, today)?));
+        }
+        result.push('\n');
+    }
+
+    Ok(result)
+}
+
+fn relative_label(day: &Date, today: &Date) -> Result<String, CliError> {
+
+    let ago = (today.to_naive()? - day.to_naive()?).num_days();
+
+    let label = match ago {
+        0 => "today".to_owned(),
+        1 => "yesterday".to_owned(),
+        -1 => "tomorrow".to_owned(),
+        ago if ago > 0 => format!("{} days ago", ago),
+        ago => format!("in {} days", -ago),
+    };
+
+    Ok(label)
+}
+
+/// Range of `stats` for one habit. Without `--from`, `--to` or `--since-created`
+/// it is `default_range` if set, otherwise the last 30 days.
+fn stats_range(matches: &ArgMatches, storage: &Storage, name: &str, clock: &dyn Clock, default_range: Option<&(Date, Date)>) -> Result<(Date, Date), CliError> {
+
+    let today = clock.today();
+    let explicit = matches.contains_id("from") || matches.contains_id("to") || matches.get_flag("since-created");
+
+    if let (Some((start, end)), false) = (default_range, explicit) {
+        return Ok((start.clone(), end.clone()));
+    }
+
+    let to = match matches.get_one::<String>("to") {
+        Some(date) => parse_date_arg(date, clock)?,
+        None => today.clone(),
+    };
+    let from = match matches.get_one::<String>("from") {
+        Some(date) => parse_date_arg(date, clock)?,
+        None if matches.get_flag("since-created") => created_or_first_marked(storage, name, &today)?,
+        None => to.sub_days(29),
+    };
+
+    Ok((from, to))
+}
+
+/// Report window from `HTRACKR_DEFAULT_RANGE`, used when no range is given.
+fn env_default_range(today: &Date) -> Result<Option<(Date, Date)>, CliError> {
+
+    match env::var("HTRACKR_DEFAULT_RANGE") {
+        Ok(value) if !value.is_empty() => Ok(Some(parse_default_range(&value, today)?)),
+        _ => Ok(None),
+    }
+}
+
+/// Range ending `today` from a value like `30d`, `this-week`, `this-month` or `this-year`.
+fn parse_default_range(value: &str, today: &Date) -> Result<(Date, Date), CliError> {
+
+    let start = match value {
+        "this-week" => today.sub_days(today.day_of_week()?.num_days_from_monday() as i64),
+        "this-month" => Date { year: today.year, month: today.month, day: 1 },
+        "this-year" => Date { year: today.year, month: 1, day: 1 },
+        days => match days.strip_suffix('d').map(|n| n.parse::<u32>()) {
+            // up to a hundred years, far enough for any history
+            Some(Ok(n)) if (1..=36500).contains(&n) => today.sub_days(n as i64 - 1),
+            _ => return Err(CliError(format!("invalid HTRACKR_DEFAULT_RANGE {}, expected like 30d, this-week, this-month or this-year", value))),
+        },
+    };
+
+    Ok((start, today.clone()))
+}
+
+fn stats(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock, locale: Locale) -> Result<(), CliError> {
+
+    let names = match matches.get_one::<String>("name") {
+        Some(name) => vec![name.clone()],
+        None if matches.get_flag("all") => storage.habit_list()?,
+        None => return Err(CliError::new("invalid args")),
+    };
+
+    let today = clock.today();
+    let default_range = env_default_range(&today)?;
+
+    let mut results = vec![];
+    for name in &names {
+        let (from, to) = stats_range(matches, storage, name, clock, default_range.as_ref())?;
+        results.push(HabitStats::compute(storage, name, &from, &to, &today)?);
+    }
+
+    if matches.get_flag("json") {
+        let json = match matches.get_flag("all") {
+            true => serde_json::to_string(&results.iter().map(|s| s.json_fields()).collect::<Vec<_>>())?,
+            false => serde_json::to_string(&results[0].json_fields())?,
+        };
+        println!("{}", json);
+        return Ok(());
+    }
+
+    if matches.get_flag("summary-only") {
+        let summary = StatsSummary::of(&results);
+        println!("{} habits, {} marks, {:.0}% average completion", summary.habits, summary.marked, summary.average_rate * 100.0);
+        return Ok(());
+    }
+
+    for stats in &results {
+        println!("{} ({} - {})", stats.name, stats.from.to_string()?, stats.to.to_string()?);
+        println!("  marked: {}/{} days ({:.0}%)", stats.marked, stats.total, stats.rate() * 100.0);
+        println!("  current streak: {}", stats.current_streak);
+        println!("  longest streak: {}", stats.longest_streak);
+
+        if matches.get_flag("weekday-breakdown") {
+            let days = storage.get_marked_days(&stats.name, &stats.from, &stats.to)?;
+            print!("{}", format_weekday_breakdown(&weekday_breakdown(&days)?, locale));
+        }
+
+        if matches.get_flag("missed") {
+            if storage.get_interval(&stats.name)? > 1 || storage.get_weekly_goal(&stats.name)?.is_some() {
+                eprintln!("warning: {} is not a daily habit, not every missed day was expected", stats.name);
+            }
+
+            let days = storage.marked_set(&stats.name, &stats.from, &stats.to)?;
+            let missed = missed_days(&stats.from, &stats.to, &days, storage.get_schedule(&stats.name)?);
+            println!("  missed: {} days", missed.len());
+            for day in missed {
+                println!("    {}", day.to_string()?);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn note(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let (Some(name), Some(date)) = (matches.get_one::<String>("name"), matches.get_one::<String>("date")) {
+        let date = parse_date_arg(date, clock)?;
+
+        match matches.get_one::<String>("text") {
+            Some(text) => storage.set_note(name, &date, text)?,
+            None => match storage.get_note(name, &date)? {
+                Some(note) => println!("{}", note),
+                None => println!("no note for {} on {}", name, date.to_string()?),
+            },
+        }
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+fn top(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let metric = Metric::parse(matches.get_one::<String>("by").map_or("streak", |m| m.as_str()))?;
+    let limit = matches.get_one::<usize>("limit").copied().unwrap_or(5);
+
+    let top = storage.most_consistent(metric, limit, &clock.today())?;
+    let width = top.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+    for (i, (name, value)) in top.iter().enumerate() {
+        let value = match metric {
+            Metric::Rate => format!("{:.0}%", value * 100.0),
+            _ => format!("{}", value),
+        };
+        println!("{}. {}{} {}", i + 1, name, str::repeat(" ", width - name.len()), value);
+    }
+
+    Ok(())
+}
+
+fn batch(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock, locale: Locale) -> Result<(), CliError> {
+
+    // read everything first, stdin must not stay locked while the commands run
+    let lines = match stdin().lock().lines().collect::<Result<Vec<String>, _>>() {
+        Ok(lines) => lines,
+        Err(err) => return Err(CliError(err.to_string())),
+    };
+
+    let continue_on_error = matches.get_flag("continue-on-error");
+    let (run, failed) = run_batch(&lines, storage, clock, locale, continue_on_error)?;
+
+    println!("{} commands run, {} failed", run, failed);
+
+    Ok(())
+}
+
+/// Runs each line as an htrackr command inside one transaction.
+/// Returns the number of commands run and failed. Without `continue_on_error`
+/// the first failure rolls everything back.
+fn run_batch(lines: &[String], storage: &Storage, clock: &dyn Clock, locale: Locale, continue_on_error: bool) -> Result<(usize, usize), CliError> {
+
+    storage.with_transaction(|s| {
+        let mut run = 0;
+        let mut failed = 0;
+
+        for (i, line) in lines.iter().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let result = split_command_line(line).and_then(|args| {
+                let matches = match create_commands().try_get_matches_from(std::iter::once("htrackr".to_owned()).chain(args)) {
+                    Ok(matches) => matches,
+                    Err(err) => return Err(CliError(err.to_string().trim().to_owned())),
+                };
+                check_batch_command(&matches)?;
+                // a failing line leaves nothing behind, even with --continue-on-error
+                s.with_savepoint(&format!("line_{}", i + 1), |s| dispatch(&matches, s, clock, locale))
+            });
+
+            run += 1;
+            if let Err(err) = result {
+                if !continue_on_error {
+                    return Err(CliError(format!("line {}: {}, nothing was changed", i + 1, err.0)));
+                }
+                eprintln!("line {}: {}", i + 1, err.0);
+                failed += 1;
+            }
+        }
+
+        Ok((run, failed))
+    })
+}
+
+/// Rejects commands that can't run in a batch: a nested batch, copies of the
+/// database which need it outside a transaction, commands that would prompt
+/// since stdin holds the batch itself, and flags of the whole run like `--db`.
+fn check_batch_command(matches: &ArgMatches) -> Result<(), CliError> {
+
+    let given = |m: &ArgMatches, id: &str| m.value_source(id) == Some(ValueSource::CommandLine);
+    for arg in create_commands().get_arguments() {
+        let id = arg.get_id().as_str();
+        let sub = matches.subcommand().filter(|_| arg.is_global_set()).map(|(_, s)| s);
+        if given(matches, id) || sub.is_some_and(|s| given(s, id)) {
+            return Err(CliError(format!("--{} applies to the whole batch, pass it to batch instead", arg.get_long().unwrap_or(id))));
+        }
+    }
+
+    match matches.subcommand() {
+        Some(("batch", _)) => Err(CliError::new("batch can't be nested")),
+        Some((name @ ("backup" | "compact"), _)) => Err(CliError(format!("{} can't run inside the batch transaction", name))),
+        Some(("delete", s)) if s.get_flag("permanent") && !s.get_flag("force") => {
+            Err(CliError::new("delete --permanent asks for confirmation, use --force in a batch"))
+        },
+        Some(("reset", s)) if !s.get_flag("yes") => Err(CliError::new("reset asks for confirmation, use --yes in a batch")),
+        _ => Ok(()),
+    }
+}
+
+/// Splits a batch line into arguments on whitespace, double quotes group words.
+fn split_command_line(line: &str) -> Result<Vec<String>, CliError> {
+
+    let mut args = vec![];
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_arg = false;
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            },
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            },
+            c => {
+                current.push(c);
+                in_arg = true;
+            },
+        }
+    }
+
+    if quoted {
+        return Err(CliError(format!("unterminated quote in {}", line)));
+    }
+    if in_arg {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+fn version(storage: &Storage) -> Result<(), CliError> {
+
+    print!("{}", version_info(Some(storage))?);
+
+    Ok(())
+}
+
+/// Prints the version without creating or migrating the database.
+fn version_readonly(db: &str) -> Result<(), CliError> {
+
+    if !Path::new(db).exists() {
+        print!("{}", version_info(None)?);
+        return Ok(());
+    }
+
+    version(&storage::connect_readonly(db)?)
+}
+
+fn version_info(storage: Option<&Storage>) -> Result<String, CliError> {
+
+    let schema = match storage {
+        Some(storage) => format!("schema version {}", storage.schema_version()?),
+        None => "no database".to_string(),
+    };
+
+    Ok(format!("htrackr {}\nsqlite {}\n{}\n", env!("CARGO_PKG_VERSION"), rusqlite::version(), schema))
+}
+
+fn status(storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    println!("{}", status_line(storage, &clock.today())?);
+
+    Ok(())
+}
+
+fn diff(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    if let (Some(first), Some(second)) = (matches.get_one::<String>("first"), matches.get_one::<String>("second")) {
+        let first = parse_date_arg(first, clock)?;
+        let second = parse_date_arg(second, clock)?;
+
+        print!("{}", format_diff(&diff_days(storage, &first, &second)?, &first, &second)?);
+        return Ok(());
+    }
+
+    Err(CliError::new("invalid args"))
+}
+
+/// Habits in list order marked on both days, only on `first` and only on `second`.
+fn diff_days(storage: &Storage, first: &Date, second: &Date) -> Result<[Vec<String>; 3], CliError> {
+
+    let on_first = storage.marked_days_in_range(first, first)?;
+    let on_second = storage.marked_days_in_range(second, second)?;
+
+    let mut result: [Vec<String>; 3] = Default::default();
+    for name in storage.habit_list()? {
+        match (on_first.contains_key(&name), on_second.contains_key(&name)) {
+            (true, true) => result[0].push(name),
+            (true, false) => result[1].push(name),
+            (false, true) => result[2].push(name),
+            (false, false) => {},
+        }
+    }
+
+    Ok(result)
+}
+
+/// The three groups of `diff_days` under labels, `-` for an empty group.
+fn format_diff(groups: &[Vec<String>; 3], first: &Date, second: &Date) -> Result<String, CliError> {
+
+    let labels = ["Both".to_owned(), format!("Only {}", first.to_string()?), format!("Only {}", second.to_string()?)];
+
+    let mut result = String::new();
+    for (label, names) in labels.iter().zip(groups) {
+        match names.is_empty() {
+            true => result.push_str(&format!("{} (0): -\n", label)),
+            false => result.push_str(&format!("{} ({}): {}\n", label, names.len(), names.join(", "))),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Number of habits, how many are done today and the best current streak.
+fn status_line(storage: &Storage, today: &Date) -> Result<String, CliError> {
+
+    let total = storage.habit_count()?;
+    let done = storage.completed_on(today)?;
+
+    let best = match storage.most_consistent(Metric::Streak, 1, today)?.first() {
+        Some((name, streak)) if *streak > 0.0 => format!("best streak {} ({})", name, streak),
+        _ => "no streaks".to_owned(),
+    };
+
+    Ok(format!("{} habits, {} done today, {}", total, done, best))
+}
+
+fn today(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let today = clock.today();
+
+    let choice = ColorChoice::parse(matches.get_one::<String>("color").map_or("never", |c| c.as_str()))?;
+    let use_color = choice.use_color(env::var("NO_COLOR").ok().as_deref(), stdout().is_terminal());
+    let long_streak = *matches.get_one::<u32>("long-streak").unwrap_or(&7);
+
+    let names = storage.habit_list()?;
+    for name in &names {
+        let marked = !storage.get_marked_days(name, &today, &today)?.is_empty();
+        let line = format!("[{}] {}", if marked { "x" } else { " " }, name);
 
-    return Err(CliError::new("invalid args"));
-}
\ No newline at end of file
+        match use_color {
+            true => println!("{}", color::paint(&line, streak_color(storage.current_streak(name, &today)?, long_streak))),
+            false => println!("{}", line),
+        }
+    }
+
+    if matches.get_flag("summary") {
+        println!("{}", format_today_summary(storage.completed_on(&today)?, names.len() as u32));
+    }
+
+    Ok(())
+}
+
+/// Color for a streak in `today`: red when broken, yellow while shorter than
+/// `long_streak` days and green after.
+fn streak_color(streak: u32, long_streak: u32) -> &'static str {
+
+    match streak {
+        0 => "red",
+        s if s < long_streak => "yellow",
+        _ => "green",
+    }
+}
+
+/// Summary line like `3/5 done (60%)`.
+fn format_today_summary(done: u32, total: u32) -> String {
+
+    match total {
+        0 => "0/0 done".to_owned(),
+        total => format!("{}/{} done ({:.0}%)", done, total, done as f64 * 100.0 / total as f64),
+    }
+}
+
+/// Creation date of the habit, falling back to its first mark and then `today`
+/// for habits created before creation dates were recorded.
+fn created_or_first_marked(storage: &Storage, name: &str, today: &Date) -> Result<Date, CliError> {
+
+    if let Some(date) = storage.created_at(name)? {
+        return Ok(date);
+    }
+
+    Ok(storage.first_marked(name)?.unwrap_or(today.clone()))
+}
+
+/// Scheduled days from `from` to `to` that are not in `marked`.
+fn missed_days(from: &Date, to: &Date, marked: &BTreeSet<Date>, schedule: Schedule) -> Vec<Date> {
+
+    DateRange::new(from, to)
+        .filter(|day| day.day_of_week().is_ok_and(|w| schedule.includes(w)))
+        .filter(|day| !marked.contains(day))
+        .collect()
+}
+
+/// Number of marked days per weekday, Monday first.
+fn weekday_breakdown(days: &[Date]) -> Result<[u32; 7], CliError> {
+
+    let mut result = [0; 7];
+    for day in days {
+        result[day.day_of_week()?.num_days_from_monday() as usize] += 1;
+    }
+
+    Ok(result)
+}
+
+fn format_weekday_breakdown(counts: &[u32; 7], locale: Locale) -> String {
+
+    let mut header = String::new();
+    let mut values = String::new();
+    for (i, count) in counts.iter().enumerate() {
+        let name = locale.weekday(Weekday::try_from(i as u8).unwrap_or(Weekday::Mon));
+        header.push_str(&format!(" {:>4}", name));
+        values.push_str(&format!(" {:>4}", count));
+    }
+
+    format!("{}\n{}\n", header, values)
+}
+
+/// All habits, or only `name`, for the json and habit-only exports.
+#[cfg(not(feature = "image"))]
+const EXPORT_FORMATS: [&str; 4] = ["ical", "json", "csv", "tsv"];
+#[cfg(feature = "image")]
+const EXPORT_FORMATS: [&str; 5] = ["ical", "json", "csv", "tsv", "png"];
+
+fn export_habits(storage: &Storage, name: Option<&String>, with_entries: bool, since: Option<&Date>) -> Result<Vec<HabitExport>, CliError> {
+
+    let mut habits = storage.export_all(with_entries, since)?;
+    if let Some(name) = name {
+        storage.resolve_habit(name)?;
+        habits.retain(|h| h.name == *name);
+    }
+
+    Ok(habits)
+}
+
+fn export(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let name = matches.get_one::<String>("name");
+
+    #[cfg(feature = "image")]
+    if matches.get_one::<String>("format").is_some_and(|f| f == "png") {
+        return export_png(matches, storage, clock);
+    }
+
+    if matches.contains_id("habit") {
+        return Err(CliError::new("only png exports take a file and a habit"));
+    }
+
+    let with_entries = !matches.get_flag("no-entries");
+    let since = match matches.get_one::<String>("since") {
+        Some(date) => Some(parse_date_arg(date, clock)?),
+        None => None,
+    };
+    let since = since.as_ref();
+
+    // entries can be large, write them as they are read unless they go to a dated file
+    let format = matches.get_one::<String>("format").and_then(|f| export::StreamFormat::parse(f));
+    if let (Some(format), true, false) = (format, with_entries, matches.contains_id("output-dir")) {
+        let name = name.map(|n| n.as_str());
+        return match matches.get_one::<String>("output") {
+            Some(path) => match fs::File::create(path) {
+                Ok(file) => storage.stream_export(&mut io::BufWriter::new(file), format, name, since),
+                Err(err) => Err(CliError(format!("failed to write {}: {}", path, err))),
+            },
+            None => storage.stream_export(&mut stdout().lock(), format, name, since),
+        };
+    }
+
+    let content = match matches.get_one::<String>("format").map(|f| f.as_str()) {
+        Some("ical") if !with_entries => return Err(CliError::new("--no-entries is not supported for ical")),
+        Some("ical") => {
+            let entries = storage.all_entries(name.map(|n| n.as_str()), since)?;
+            let stamp = clock.now().format("%Y%m%dT%H%M%SZ").to_string();
+            ical::to_ical(&entries, &stamp)?
+        },
+        Some("json") => {
+            let mut json = export::to_json(&export_habits(storage, name, with_entries, since)?)?;
+            json.push('\n');
+            json
+        },
+        Some("csv") if !with_entries => export::habits_to_csv(&export_habits(storage, name, with_entries, since)?),
+        Some("tsv") if !with_entries => export::habits_to_tsv(&export_habits(storage, name, with_entries, since)?),
+        Some("csv") => export::to_csv(&storage.all_entries(name.map(|n| n.as_str()), since)?)?,
+        Some("tsv") => export::to_tsv(&storage.all_entries(name.map(|n| n.as_str()), since)?)?,
+        _ => return Err(CliError::new("invalid format")),
+    };
+
+    if let Some(dir) = matches.get_one::<String>("output-dir") {
+        let format = matches.get_one::<String>("format").map_or("ical", |f| f.as_str());
+        let path = write_to_dir(dir, format, &clock.today(), content.as_bytes())?;
+        println!("Exported to {}", path.display());
+        return Ok(());
+    }
+
+    match matches.get_one::<String>("output") {
+        Some(path) => {
+            if let Err(err) = fs::write(path, content) {
+                return Err(CliError(format!("failed to write {}: {}", path, err)));
+            }
+        },
+        None => print!("{}", content),
+    }
+
+    Ok(())
+}
+
+/// Writes this year's heatmap of one habit, given as `<file> <name>` or as the
+/// name with `--output` or `--output-dir`.
+#[cfg(feature = "image")]
+fn export_png(matches: &ArgMatches, storage: &Storage, clock: &dyn Clock) -> Result<(), CliError> {
+
+    let output = matches.get_one::<String>("output");
+    let output_dir = matches.get_one::<String>("output-dir");
+    let (path, name) = match (matches.get_one::<String>("name"), matches.get_one::<String>("habit")) {
+        (Some(path), Some(name)) if output.is_none() && output_dir.is_none() => (Some(path), name),
+        (Some(name), None) if output.is_some() || output_dir.is_some() => (output, name),
+        _ => return Err(CliError::new("png export needs a file and a habit, or a habit with --output or --output-dir")),
+    };
+
+    let today = clock.today();
+    let first = Date { year: today.year, month: 1, day: 1 };
+    let last = Date { year: today.year, month: 12, day: 31 };
+    let png = crate::png::heatmap(today.year, &storage.marked_set(name, &first, &last)?)?;
+
+    match (path, output_dir) {
+        (Some(path), _) => {
+            if let Err(err) = fs::write(path, png) {
+                return Err(CliError(format!("failed to write {}: {}", path, err)));
+            }
+        },
+        (None, Some(dir)) => {
+            let path = write_to_dir(dir, "png", &today, &png)?;
+            println!("Exported to {}", path.display());
+        },
+        (None, None) => return Err(CliError::new("png export needs a file")),
+    }
+
+    Ok(())
+}
+
+/// File name for an export made on `today`, like `htrackr-backup-2024-03-07.json`.
+fn export_filename(format: &str, today: &Date) -> Result<String, CliError> {
+
+    let extension = match format {
+        "ical" => "ics",
+        other => other,
+    };
+
+    Ok(format!("htrackr-backup-{}.{}", today.to_string()?, extension))
+}
+
+/// Writes `content` to a dated file in `dir`, creating the directory if needed.
+fn write_to_dir(dir: &str, format: &str, today: &Date, content: &[u8]) -> Result<PathBuf, CliError> {
+
+    if let Err(err) = fs::create_dir_all(dir) {
+        return Err(CliError(format!("failed to create {}: {}", dir, err)));
+    }
+
+    let path = Path::new(dir).join(export_filename(format, today)?);
+    if let Err(err) = fs::write(&path, content) {
+        return Err(CliError(format!("failed to write {}: {}", path.display(), err)));
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::storage::connect_test;
+
+    #[test]
+    fn test_parse_date_arg() {
+        let clock = FixedClock::at(2024, 1, 1);
+
+        assert_eq!(parse_date_arg("y", &clock).unwrap(), Date { year: 2023, month: 12, day: 31 });
+        assert_eq!(parse_date_arg("yesterday", &clock).unwrap(), Date { year: 2023, month: 12, day: 31 });
+        assert_eq!(parse_date_arg("2024-02-03", &clock).unwrap(), Date { year: 2024, month: 2, day: 3 });
+        assert_eq!(parse_date_arg("2025-12-31", &clock).unwrap(), Date { year: 2025, month: 12, day: 31 });
+
+        assert!(parse_date_arg("9999-12-31", &clock).is_err());
+        assert!(parse_date_arg("2026-01-01", &clock).is_err());
+        assert!(parse_date_arg("0001-01-01", &clock).is_err());
+        assert!(parse_date_arg("1969-12-31", &clock).is_err());
+        assert_eq!(parse_date_arg("today", &clock).unwrap(), clock.today());
+
+        assert!(looks_like_date("2024-02-30"));
+        assert!(looks_like_date("9999-12-31..yesterday"));
+        assert!(!looks_like_date("2024"));
+        assert!(!looks_like_date("gym"));
+    }
+
+    #[test]
+    fn test_mark_date_bounds() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+        storage.create_habit("read").unwrap();
+
+        let mark_args = |args: &[&str]| {
+            let matches = create_commands().try_get_matches_from(["htrackr", "mark"].iter().chain(args)).unwrap();
+            let (_, matches) = matches.subcommand().unwrap();
+            mark(matches, &storage, &clock)
+        };
+
+        assert!(mark_args(&["read", "9999-12-31"]).unwrap_err().0.contains("out of range"));
+        assert!(mark_args(&["read", "0001-01-01"]).unwrap_err().0.contains("out of range"));
+        assert!(mark_args(&["read", "1969-12-31..1970-01-02"]).unwrap_err().0.contains("out of range"));
+        assert!(mark_args(&["--skip-missing", "read", "2024-02-30"]).is_err());
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 0);
+
+        mark_args(&["read", "2024-03-01"]).unwrap();
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_configured_date_bounds() {
+        let clock = SystemClock {
+            zone: Zone::Utc,
+            min_date: Some(Date { year: 2020, month: 1, day: 15 }),
+            max_date: Some(Date { year: 2030, month: 6, day: 30 }),
+        };
+
+        assert!(parse_date_arg("2020-01-14", &clock).is_err());
+        assert!(parse_date_arg("2020-01-15", &clock).is_ok());
+        assert!(parse_date_arg("2030-07-01", &clock).is_err());
+        assert_eq!(month_arg(Some(&"2020-01".to_owned()), &clock).unwrap(), (2020, 1));
+        assert!(month_arg(Some(&"2019-12".to_owned()), &clock).is_err());
+        assert!(month_arg(Some(&"2030-07".to_owned()), &clock).is_err());
+    }
+
+    #[test]
+    fn test_month_counts() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        for (year, month, day) in [(2023, 11, 30), (2024, 2, 1), (2024, 2, 9), (2024, 2, 10), (2024, 3, 1)] {
+            storage.mark_habit("read", &Date { year, month, day }).unwrap();
+        }
+
+        let first = Date { year: 2023, month: 11, day: 30 };
+        let last = Date { year: 2024, month: 3, day: 1 };
+        for page_size in [1, 2, 1000] {
+            assert_eq!(month_counts(&storage, "read", &first, &last, page_size).unwrap(), vec![1, 0, 0, 3, 1]);
+        }
+    }
+
+    #[test]
+    fn test_confirm_delete() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+
+        assert_eq!(confirm_delete(&storage, "read", &mut "".as_bytes()).unwrap(), None);
+        assert!(storage.habit_exists("read").unwrap());
+
+        assert_eq!(confirm_delete(&storage, "read", &mut "n\n".as_bytes()).unwrap(), Some(false));
+        assert!(storage.habit_exists("read").unwrap());
+
+        assert_eq!(confirm_delete(&storage, "read", &mut "y\n".as_bytes()).unwrap(), Some(true));
+        assert!(!storage.habit_exists("read").unwrap());
+    }
+
+    #[test]
+    fn test_format_today_summary() {
+        assert_eq!(format_today_summary(3, 5), "3/5 done (60%)");
+        assert_eq!(format_today_summary(0, 1), "0/1 done (0%)");
+        assert_eq!(format_today_summary(0, 0), "0/0 done");
+    }
+
+    #[test]
+    fn test_status_line() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        assert_eq!(status_line(&storage, &today).unwrap(), "0 habits, 0 done today, no streaks");
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.mark_habit("read", &today).unwrap();
+        storage.mark_habit("gym", &today).unwrap();
+        storage.mark_habit("gym", &today.sub_days(1)).unwrap();
+
+        assert_eq!(status_line(&storage, &today).unwrap(), "3 habits, 2 done today, best streak gym (2)");
+    }
+
+    #[test]
+    fn test_relative_label() {
+        let today = Date { year: 2024, month: 3, day: 1 };
+
+        assert_eq!(relative_label(&today, &today).unwrap(), "today");
+        assert_eq!(relative_label(&today.sub_days(1), &today).unwrap(), "yesterday");
+        assert_eq!(relative_label(&today.sub_days(5), &today).unwrap(), "5 days ago");
+        assert_eq!(relative_label(&today.add_days(1), &today).unwrap(), "tomorrow");
+        assert_eq!(relative_label(&today.add_days(3), &today).unwrap(), "in 3 days");
+
+        let days = vec![today.sub_days(1)];
+        assert_eq!(format_log(&days, false, Some(&today), Locale::En).unwrap(), "2024-02-29 Thu (yesterday)\n");
+    }
+
+    #[test]
+    fn test_version_info() {
+        let storage = connect_test().unwrap();
+        let info = version_info(Some(&storage)).unwrap();
+
+        assert!(info.starts_with(&format!("htrackr {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(info.contains(&format!("sqlite {}", rusqlite::version())));
+        assert!(info.contains("schema version "));
+
+        assert!(version_info(None).unwrap().ends_with("\nno database\n"));
+    }
+
+    #[test]
+    fn test_version_readonly() {
+        let db = format!("./db_test/{}", uuid::Uuid::new_v4());
+        version_readonly(&db).unwrap();
+        assert!(!Path::new(&db).exists());
+    }
+
+    #[test]
+    fn test_write_to_dir() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 7 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &today).unwrap();
+
+        assert_eq!(export_filename("json", &today).unwrap(), "htrackr-backup-2024-03-07.json");
+        assert_eq!(export_filename("ical", &today).unwrap(), "htrackr-backup-2024-03-07.ics");
+
+        let dir = format!("./db_test/{}", uuid::Uuid::new_v4());
+        let content = export::to_json(&storage.export_all(true, None).unwrap()).unwrap();
+        let path = write_to_dir(&dir, "json", &today, content.as_bytes()).unwrap();
+
+        assert_eq!(path, Path::new(&dir).join("htrackr-backup-2024-03-07.json"));
+        assert_eq!(export::from_json(&fs::read_to_string(&path).unwrap()).unwrap()[0].entries, vec![today]);
+    }
+
+    #[test]
+    fn test_export_ical_stamp() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 7);
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &clock.today()).unwrap();
+
+        let dir = format!("./db_test/{}", uuid::Uuid::new_v4());
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/read.ics", dir);
+        let matches = create_commands().try_get_matches_from(["htrackr", "export", "--format", "ical", "--output", &path]).unwrap();
+        let (_, matches) = matches.subcommand().unwrap();
+        export(matches, &storage, &clock).unwrap();
+
+        let ics = fs::read_to_string(&path).unwrap();
+        assert!(ics.contains("DTSTAMP:20240307T120000Z"));
+        assert_eq!(ics, ical::to_ical(&storage.all_entries(None, None).unwrap(), "20240307T120000Z").unwrap());
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_export_png() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 7);
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &clock.today()).unwrap();
+
+        let dir = format!("./db_test/{}", uuid::Uuid::new_v4());
+        fs::create_dir_all(&dir).unwrap();
+        let run = |args: &[&str]| {
+            let matches = create_commands().try_get_matches_from(["htrackr", "export", "--format", "png"].iter().chain(args)).unwrap();
+            let (_, matches) = matches.subcommand().unwrap();
+            export(matches, &storage, &clock)
+        };
+
+        let path = format!("{}/read.png", dir);
+        run(&[&path, "read"]).unwrap();
+        run(&["read", "--output-dir", &dir]).unwrap();
+        for path in [path, format!("{}/htrackr-backup-2024-03-07.png", dir)] {
+            let png = fs::read(path).unwrap();
+            assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+            assert!(u32::from_be_bytes(png[16..20].try_into().unwrap()) > 0);
+            assert!(u32::from_be_bytes(png[20..24].try_into().unwrap()) > 0);
+        }
+
+        assert!(run(&["read"]).is_err());
+        assert!(run(&[&format!("{}/x.png", dir), "read", "--output-dir", &dir]).is_err());
+    }
+
+    #[test]
+    fn test_missed_days() {
+        let from = Date { year: 2024, month: 2, day: 27 };
+        let to = Date { year: 2024, month: 3, day: 2 };
+        let marked = BTreeSet::from([
+            Date { year: 2024, month: 2, day: 27 },
+            Date { year: 2024, month: 3, day: 1 },
+            Date { year: 2024, month: 3, day: 5 },
+        ]);
+
+        assert_eq!(missed_days(&from, &to, &marked, Schedule::Daily), vec![
+            Date { year: 2024, month: 2, day: 28 },
+            Date { year: 2024, month: 2, day: 29 },
+            Date { year: 2024, month: 3, day: 2 },
+        ]);
+        assert!(missed_days(&to, &from, &marked, Schedule::Daily).is_empty());
+
+        // the 2nd is a saturday
+        assert_eq!(missed_days(&from, &to, &marked, Schedule::Weekdays).len(), 2);
+        assert_eq!(missed_days(&from, &to, &marked, Schedule::Weekends), vec![Date { year: 2024, month: 3, day: 2 }]);
+    }
+
+    #[test]
+    fn test_format_activity() {
+        let totals = vec![
+            (Date { year: 2024, month: 3, day: 1 }, 2),
+            (Date { year: 2024, month: 3, day: 2 }, 0),
+        ];
+
+        assert_eq!(format_activity(&totals).unwrap(), "2024-03-01   2 ██\n2024-03-02   0\n");
+    }
+
+    #[test]
+    fn test_parse_default_month() {
+        let clock = FixedClock::at(2024, 3, 4);
+
+        assert_eq!(parse_default_month("current", &clock).unwrap(), (2024, 3));
+        assert_eq!(parse_default_month("previous", &clock).unwrap(), (2024, 2));
+        assert_eq!(parse_default_month("previous", &FixedClock::at(2024, 1, 20)).unwrap(), (2023, 12));
+        assert_eq!(parse_default_month("2023-7", &clock).unwrap(), (2023, 7));
+
+        assert_eq!(parse_default_month("previous-until-5", &clock).unwrap(), (2024, 2));
+        assert_eq!(parse_default_month("previous-until-4", &clock).unwrap(), (2024, 2));
+        assert_eq!(parse_default_month("previous-until-3", &clock).unwrap(), (2024, 3));
+
+        assert!(parse_default_month("2024-13", &clock).is_err());
+        assert!(parse_default_month("1800-01", &clock).is_err());
+        assert!(parse_default_month("previous-until-0", &clock).is_err());
+        assert!(parse_default_month("next", &clock).is_err());
+    }
+
+    #[test]
+    fn test_parse_default_range() {
+        // a sunday
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        assert_eq!(parse_default_range("7d", &today).unwrap(), (Date { year: 2024, month: 3, day: 4 }, today.clone()));
+        assert_eq!(parse_default_range("1d", &today).unwrap(), (today.clone(), today.clone()));
+        assert_eq!(parse_default_range("this-week", &today).unwrap().0, Date { year: 2024, month: 3, day: 4 });
+        assert_eq!(parse_default_range("this-month", &today).unwrap().0, Date { year: 2024, month: 3, day: 1 });
+        assert_eq!(parse_default_range("this-year", &today).unwrap().0, Date { year: 2024, month: 1, day: 1 });
+
+        assert!(parse_default_range("0d", &today).is_err());
+        assert_eq!(parse_default_range("36500d", &today).unwrap().0, today.sub_days(36499));
+        assert!(parse_default_range("36501d", &today).is_err());
+        assert!(parse_default_range("4000000000d", &today).is_err());
+        assert!(parse_default_range("30", &today).is_err());
+        assert!(parse_default_range("last-month", &today).is_err());
+    }
+
+    #[test]
+    fn test_stats_default_range() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+        storage.create_habit("read").unwrap();
+
+        let default = parse_default_range("this-month", &clock.today()).unwrap();
+        let range = |args: &[&str], default: Option<&(Date, Date)>| {
+            let matches = create_commands().try_get_matches_from(args).unwrap();
+            let (_, matches) = matches.subcommand().unwrap();
+            stats_range(matches, &storage, "read", &clock, default).unwrap()
+        };
+
+        assert_eq!(range(&["htrackr", "stats", "read"], None).0, Date { year: 2024, month: 2, day: 10 });
+        assert_eq!(range(&["htrackr", "stats", "read"], Some(&default)), default);
+        assert_eq!(range(&["htrackr", "stats", "read", "--from", "2024-03-08"], Some(&default)).0, Date { year: 2024, month: 3, day: 8 });
+    }
+
+    #[test]
+    fn test_streak_color() {
+        assert_eq!(streak_color(0, 7), "red");
+        assert_eq!(streak_color(1, 7), "yellow");
+        assert_eq!(streak_color(6, 7), "yellow");
+        assert_eq!(streak_color(7, 7), "green");
+        assert_eq!(streak_color(30, 7), "green");
+        assert_eq!(streak_color(1, 1), "green");
+        assert_eq!(streak_color(0, 0), "red");
+    }
+
+    #[test]
+    fn test_run_batch() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+
+        let lines = |input: &str| input.lines().map(|line| line.to_owned()).collect::<Vec<String>>();
+
+        let input = lines("create read\n\n# comment\ncreate \"long walk\"\nmark read 2024-03-01\nmark \"long walk\"\n");
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, false).unwrap(), (4, 0));
+        assert_eq!(storage.habit_list().unwrap(), vec!["read", "long walk"]);
+        assert_eq!(storage.count_entries("long walk", None, None).unwrap(), 1);
+
+        let input = lines("create gym\nmark gym\nmark missing\ncreate swim --tag \" \"\n");
+        assert!(run_batch(&input, &storage, &clock, Locale::En, false).is_err());
+        assert!(!storage.habit_exists("gym").unwrap());
+
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, true).unwrap(), (4, 2));
+        assert!(storage.habit_exists("gym").unwrap());
+        // created before its tag failed, rolled back with the line
+        assert!(!storage.habit_exists("swim").unwrap());
+
+        // prompts would read from the batch input
+        let input = lines("delete gym --permanent\nreset\nbatch\nbackup copy.db\ncompact copy.db");
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, true).unwrap(), (5, 5));
+        assert!(storage.habit_exists("gym").unwrap());
+
+        let input = lines("delete gym --permanent --force");
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, false).unwrap(), (1, 0));
+        assert!(!storage.habit_exists("gym").unwrap());
+
+        // flags of the whole run would be ignored on a line
+        let input = lines("--utc create z
+create z --db other.db
+create z --readonly
+--version create z");
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, true).unwrap(), (4, 4));
+        assert!(!storage.habit_exists("z").unwrap());
+    }
+
+    #[test]
+    fn test_run_batch_import() {
+        let source = connect_test().unwrap();
+        source.create_habit("swim").unwrap();
+        source.mark_habit("swim", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+
+        let dir = format!("./db_test/{}", uuid::Uuid::new_v4());
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/e.json", dir);
+        fs::write(&path, export::to_json(&source.export_all(true, None).unwrap()).unwrap()).unwrap();
+
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+        let input = vec!["create read".to_owned(), format!("import {} --dry-run", path), "create walk".to_owned()];
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, false).unwrap(), (3, 0));
+        assert_eq!(storage.habit_list().unwrap(), vec!["read", "walk"]);
+
+        let input = vec![format!("import {}", path), "mark swim 2024-03-02".to_owned()];
+        assert_eq!(run_batch(&input, &storage, &clock, Locale::En, false).unwrap(), (2, 0));
+        assert_eq!(storage.count_entries("swim", None, None).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_delete_force_requires_permanent() {
+        assert!(create_commands().try_get_matches_from(["htrackr", "delete", "gym", "--force"]).is_err());
+        assert!(create_commands().try_get_matches_from(["htrackr", "delete", "gym", "--permanent", "--force"]).is_ok());
+    }
+
+    #[test]
+    fn test_day_window() {
+        assert_eq!(day_window(None, None, 2024, 5).unwrap(), None);
+        assert_eq!(day_window(Some(10), Some(20), 2024, 5).unwrap(), Some((10, 20)));
+        assert_eq!(day_window(Some(10), None, 2024, 2).unwrap(), Some((10, 29)));
+        assert_eq!(day_window(None, Some(5), 2024, 5).unwrap(), Some((1, 5)));
+        assert_eq!(day_window(Some(7), Some(7), 2024, 5).unwrap(), Some((7, 7)));
+
+        assert!(day_window(Some(30), None, 2023, 2).is_err());
+        assert!(day_window(Some(0), None, 2024, 5).is_err());
+        assert!(day_window(None, Some(32), 2024, 5).is_err());
+        assert!(day_window(Some(20), Some(10), 2024, 5).is_err());
+    }
+
+    #[test]
+    fn test_diff_days() {
+        let storage = connect_test().unwrap();
+        for name in ["read", "gym", "walk", "swim"] {
+            storage.create_habit(name).unwrap();
+        }
+        let first = Date { year: 2024, month: 5, day: 1 };
+        let second = Date { year: 2024, month: 5, day: 2 };
+        storage.batch_mark(&[
+            ("read".to_owned(), first.clone()),
+            ("read".to_owned(), second.clone()),
+            ("gym".to_owned(), first.clone()),
+            ("walk".to_owned(), second.clone()),
+        ]).unwrap();
+
+        let groups = diff_days(&storage, &first, &second).unwrap();
+        assert_eq!(groups, [vec!["read".to_owned()], vec!["gym".to_owned()], vec!["walk".to_owned()]]);
+
+        assert_eq!(format_diff(&groups, &first, &second).unwrap(), "Both (1): read\nOnly 2024-05-01 (1): gym\nOnly 2024-05-02 (1): walk\n");
+        assert_eq!(format_diff(&Default::default(), &first, &second).unwrap(), "Both (0): -\nOnly 2024-05-01 (0): -\nOnly 2024-05-02 (0): -\n");
+    }
+
+    #[test]
+    fn test_hook_args() {
+        let date = Date { year: 2024, month: 3, day: 1 };
+
+        assert_eq!(hook_args("notify-send \"{habit} done\" {date}", "read books", &date).unwrap(), vec!["notify-send", "read books done", "2024-03-01"]);
+        assert_eq!(hook_args("curl -d habit={habit}&day={date} {url}", "gym", &date).unwrap(), vec!["curl", "-d", "habit=gym&day=2024-03-01", "{url}"]);
+        assert!(hook_args("echo \"{habit}", "gym", &date).is_err());
+    }
+
+    #[test]
+    fn test_split_command_line() {
+        assert_eq!(split_command_line("mark read  y").unwrap(), vec!["mark", "read", "y"]);
+        assert_eq!(split_command_line("note read y \"two words\"").unwrap(), vec!["note", "read", "y", "two words"]);
+        assert_eq!(split_command_line("create \"\"").unwrap(), vec!["create", ""]);
+        assert!(split_command_line("create \"read").is_err());
+    }
+
+    #[test]
+    fn test_parse_yes_no() {
+        assert!(parse_yes_no("y\n"));
+        assert!(parse_yes_no("yes\n"));
+        assert!(parse_yes_no("Y\n"));
+        assert!(parse_yes_no("YES"));
+        assert!(!parse_yes_no("n\n"));
+        assert!(!parse_yes_no("no\n"));
+        assert!(!parse_yes_no("\n"));
+        assert!(!parse_yes_no(""));
+        assert!(!parse_yes_no("yep\n"));
+    }
+
+    #[test]
+    fn test_only_habits() {
+        let storage = connect_test().unwrap();
+        for name in ["read", "gym", "walk"] {
+            storage.create_habit(name).unwrap();
+        }
+        let list = storage.habit_list().unwrap();
+
+        assert_eq!(only_habits(&storage, list.clone(), &["gym".to_owned()]).unwrap(), vec!["gym"]);
+        assert_eq!(only_habits(&storage, list.clone(), &["walk".to_owned(), "read".to_owned()]).unwrap(), vec!["read", "walk"]);
+        assert!(only_habits(&storage, list, &["gym".to_owned(), "run".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_exclude_habits() {
+        let list = vec!["read".to_owned(), "gym".to_owned(), "walk".to_owned()];
+
+        assert_eq!(exclude_habits(list.clone(), &["gym".to_owned(), "read".to_owned()]), vec!["walk"]);
+        assert_eq!(exclude_habits(list, &["missing".to_owned()]).len(), 3);
+    }
+
+    #[test]
+    fn test_filter_min_streak() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        for day in 6..=10 {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        storage.mark_habit("gym", &Date { year: 2024, month: 3, day: 10 }).unwrap();
+
+        let list = storage.habit_list().unwrap();
+        assert_eq!(filter_min_streak(list.clone(), &storage, &today, 0).unwrap(), vec!["read", "gym", "walk"]);
+        assert_eq!(filter_min_streak(list.clone(), &storage, &today, 1).unwrap(), vec!["read", "gym"]);
+        assert_eq!(filter_min_streak(list, &storage, &today, 3).unwrap(), vec!["read"]);
+    }
+
+    #[test]
+    fn test_mark_habits_skip_missing() {
+        let storage = connect_test().unwrap();
+        let dates = vec![Date { year: 2024, month: 3, day: 10 }];
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+
+        let names = vec!["read".to_owned(), "deleted".to_owned(), "gym".to_owned()];
+        assert!(mark_habits(&storage, &names, &dates, false, false).is_err());
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 0);
+
+        let (_, skipped) = mark_habits(&storage, &names, &dates, true, false).unwrap();
+        assert_eq!(skipped, vec!["deleted"]);
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 1);
+        assert_eq!(storage.count_entries("gym", None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mark_habits_summary() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 2 }).unwrap();
+
+        let names = vec!["read".to_owned()];
+        let dates = parse_dates("2024-03-01..2024-03-04", &clock).unwrap();
+        let (summary, _) = mark_habits(&storage, &names, &dates, false, false).unwrap();
+        assert_eq!((summary.inserted, summary.skipped), (3, 1));
+        assert_eq!(summary.marked.len(), 3);
+        assert!(!summary.marked.contains(&("read".to_owned(), Date { year: 2024, month: 3, day: 2 })));
+        assert_eq!(format_mark_summary(&summary), "marked 3, skipped 1 (already marked)");
+
+        let (summary, _) = mark_habits(&storage, &names, &[clock.today()], false, false).unwrap();
+        assert_eq!(format_mark_summary(&summary), "marked 1");
+    }
+
+    #[test]
+    fn test_mark_habits_cron() {
+        let storage = connect_test().unwrap();
+        let names = vec!["read".to_owned()];
+        let dates = vec![Date { year: 2024, month: 3, day: 10 }];
+
+        storage.create_habit("read").unwrap();
+        mark_habits(&storage, &names, &dates, false, true).unwrap();
+        assert!(mark_habits(&storage, &names, &dates, false, false).is_err());
+
+        let (summary, _) = mark_habits(&storage, &names, &dates, false, true).unwrap();
+        assert_eq!(summary, BatchSummary { inserted: 0, skipped: 1, marked: vec![] });
+        assert_eq!(mark_message(&summary, true), None);
+        assert_eq!(mark_message(&summary, false).unwrap(), "marked 0, skipped 1 (already marked)");
+
+        assert!(mark_habits(&storage, &["missing".to_owned()], &dates, false, true).is_err());
+    }
+
+    #[test]
+    fn test_order_log() {
+        let days = (1..=5).map(|day| Date { year: 2024, month: 3, day }).collect::<Vec<Date>>();
+        let day = |day| Date { year: 2024, month: 3, day };
+
+        assert_eq!(order_log(days.clone(), true, Some(3)), vec![day(5), day(4), day(3)]);
+        assert_eq!(order_log(days.clone(), false, Some(2)), vec![day(4), day(5)]);
+        assert_eq!(order_log(days.clone(), true, None).len(), 5);
+        assert_eq!(order_log(days.clone(), true, None)[0], day(5));
+        assert_eq!(order_log(days, false, Some(10)).len(), 5);
+    }
+
+    #[test]
+    fn test_format_log_plain_dates() {
+        let days = vec![
+            Date { year: 2024, month: 3, day: 1 },
+            Date { year: 2024, month: 3, day: 10 },
+        ];
+
+        let plain = format_log(&days, true, None, Locale::En).unwrap();
+        assert_eq!(plain, "2024-03-01\n2024-03-10\n");
+        for line in plain.lines() {
+            assert!(Date::from_string(line).is_ok());
+            assert_eq!(line.len(), 10);
+        }
+
+        assert_eq!(format_log(&days, false, None, Locale::En).unwrap(), "2024-03-01 Fri\n2024-03-10 Sun\n");
+        assert_eq!(format_log(&days, false, None, Locale::De).unwrap(), "2024-03-01 Fr\n2024-03-10 So\n");
+    }
+
+    #[test]
+    fn test_weekday_breakdown() {
+        let days = vec![
+            Date { year: 2024, month: 3, day: 4 },  // Mon
+            Date { year: 2024, month: 3, day: 11 }, // Mon
+            Date { year: 2024, month: 3, day: 6 },  // Wed
+            Date { year: 2024, month: 3, day: 10 }, // Sun
+        ];
+
+        let counts = weekday_breakdown(&days).unwrap();
+        assert_eq!(counts, [2, 0, 1, 0, 0, 0, 1]);
+
+        let table = format_weekday_breakdown(&counts, Locale::En);
+        assert_eq!(table, "  Mon  Tue  Wed  Thu  Fri  Sat  Sun\n    2    0    1    0    0    0    1\n");
+
+        let table = format_weekday_breakdown(&counts, Locale::De);
+        assert!(table.starts_with("   Mo   Di   Mi   Do   Fr   Sa   So\n"));
+    }
+
+    #[test]
+    fn test_create_with_backfill() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 1 };
+
+        create_with_backfill(&storage, "read", 3, &today).unwrap();
+
+        let days = storage.get_marked_days("read", &Date { year: 2024, month: 1, day: 1 }, &today).unwrap();
+        assert_eq!(days.len(), 3);
+        assert!(days.contains(&Date { year: 2024, month: 2, day: 28 }));
+        assert!(days.contains(&Date { year: 2024, month: 2, day: 29 }));
+        assert!(days.contains(&today));
+
+        assert!(create_with_backfill(&storage, "gym", 0, &today).is_err());
+        assert!(create_with_backfill(&storage, "gym", 1000, &today).is_err());
+        assert!(!storage.habit_exists("gym").unwrap());
+        assert_eq!(storage.created_at("read").unwrap(), Some(Date { year: 2024, month: 2, day: 28 }));
+    }
+
+    #[test]
+    fn test_created_or_first_marked() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        assert_eq!(created_or_first_marked(&storage, "read", &today).unwrap(), today);
+
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 4 }).unwrap();
+        assert_eq!(created_or_first_marked(&storage, "read", &today).unwrap(), Date { year: 2024, month: 3, day: 4 });
+
+        let created = today.sub_days(4);
+        storage.set_created_at("read", &created).unwrap();
+        let from = created_or_first_marked(&storage, "read", &today).unwrap();
+        assert_eq!(from, created);
+        assert_eq!(date::days_between(&from, &today).unwrap().len(), 5);
+        assert_eq!(storage.get_marked_days("read", &from, &today).unwrap().len(), 0);
+
+        storage.set_created_at("read", &today).unwrap();
+        let from = created_or_first_marked(&storage, "read", &today).unwrap();
+        assert_eq!(date::days_between(&from, &today).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_stats_since_created() {
+        let storage = connect_test().unwrap();
+        let clock = FixedClock::at(2024, 3, 10);
+        let today = clock.today();
+
+        let since_created = |name: &str| {
+            let matches = create_commands().try_get_matches_from(["htrackr", "stats", name, "--since-created"]).unwrap();
+            let (_, matches) = matches.subcommand().unwrap();
+            let (from, to) = stats_range(matches, &storage, name, &clock, None).unwrap();
+            HabitStats::compute(&storage, name, &from, &to, &today).unwrap()
+        };
+
+        // created 10 days ago, marked on 4 of them
+        storage.create_habit("read").unwrap();
+        storage.set_created_at("read", &today.sub_days(9)).unwrap();
+        for days_ago in [0, 1, 5, 9] {
+            storage.mark_habit("read", &today.sub_days(days_ago)).unwrap();
+        }
+        let stats = since_created("read");
+        assert_eq!((&stats.from, stats.marked, stats.total), (&today.sub_days(9), 4, 10));
+        assert_eq!(stats.rate(), 0.4);
+
+        storage.create_habit("gym").unwrap();
+        storage.set_created_at("gym", &today).unwrap();
+        let stats = since_created("gym");
+        assert_eq!((stats.marked, stats.total), (0, 1));
+        assert_eq!(stats.rate(), 0.0);
+
+        storage.mark_habit("gym", &today).unwrap();
+        let stats = since_created("gym");
+        assert_eq!((stats.marked, stats.total), (1, 1));
+        assert_eq!(stats.rate(), 1.0);
+    }
+}
@@ -0,0 +1,144 @@
+use chrono::Weekday;
+
+use crate::{error::CliError, storage::Schedule};
+
+/// User settings, stored as key/value rows in the `settings` table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Prefix of generated habit ids.
+    pub id_prefix: String,
+    /// Build habit ids from a slug of the name plus a short random suffix
+    /// instead of a full UUID.
+    pub id_slug: bool,
+    /// Reject habit names with non-ASCII characters.
+    pub names_ascii_only: bool,
+    /// First column of the calendar in `show`, Monday or Sunday.
+    pub week_start: Weekday,
+    /// Settings given to habits made with `create`.
+    pub habit_defaults: HabitDefaults,
+    /// Grid colors of `list --color`.
+    pub palette: PaletteNames,
+    /// Command run after `mark`, with `{habit}` and `{date}` filled in.
+    pub mark_hook: Option<String>,
+}
+
+/// Color names of the `palette.*` settings, looked up by `color::Palette::parse`
+/// so unknown names only warn.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PaletteNames {
+    pub marked: Option<String>,
+    pub empty: Option<String>,
+    pub weekend: Option<String>,
+}
+
+/// Defaults for new habits, each can be overridden on `create`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HabitDefaults {
+    pub tag: Option<String>,
+    pub schedule: Schedule,
+}
+
+impl Default for HabitDefaults {
+    fn default() -> Self {
+        HabitDefaults {
+            tag: None,
+            schedule: Schedule::Daily,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            id_prefix: "hbt_".to_owned(),
+            id_slug: false,
+            names_ascii_only: false,
+            week_start: Weekday::Mon,
+            habit_defaults: HabitDefaults::default(),
+            palette: PaletteNames::default(),
+            mark_hook: None,
+        }
+    }
+}
+
+pub const KEYS: [&str; 10] = [
+    "id.prefix", "id.slug", "names.ascii_only", "week.first_day", "defaults.tag", "defaults.schedule",
+    "palette.marked", "palette.empty", "palette.weekend", "hooks.mark",
+];
+
+impl Config {
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), CliError> {
+        match key {
+            "id.prefix" => self.id_prefix = value.to_owned(),
+            "id.slug" => self.id_slug = parse_bool(key, value)?,
+            "names.ascii_only" => self.names_ascii_only = parse_bool(key, value)?,
+            "week.first_day" => self.week_start = parse_week_start(value)?,
+            "defaults.tag" => self.habit_defaults.tag = optional(value),
+            "defaults.schedule" => self.habit_defaults.schedule = Schedule::parse(value)?,
+            "palette.marked" => self.palette.marked = optional(value),
+            "palette.empty" => self.palette.empty = optional(value),
+            "palette.weekend" => self.palette.weekend = optional(value),
+            "hooks.mark" => self.mark_hook = optional(value),
+            _ => return Err(CliError(format!("unknown setting {}, expected one of {}", key, KEYS.join(", ")))),
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<String, CliError> {
+        match key {
+            "id.prefix" => Ok(self.id_prefix.clone()),
+            "id.slug" => Ok(self.id_slug.to_string()),
+            "names.ascii_only" => Ok(self.names_ascii_only.to_string()),
+            "week.first_day" => Ok(week_start_name(self.week_start).to_owned()),
+            "defaults.tag" => Ok(self.habit_defaults.tag.clone().unwrap_or_default()),
+            "defaults.schedule" => Ok(self.habit_defaults.schedule.name().to_owned()),
+            "palette.marked" => Ok(self.palette.marked.clone().unwrap_or_default()),
+            "palette.empty" => Ok(self.palette.empty.clone().unwrap_or_default()),
+            "palette.weekend" => Ok(self.palette.weekend.clone().unwrap_or_default()),
+            "hooks.mark" => Ok(self.mark_hook.clone().unwrap_or_default()),
+            _ => Err(CliError(format!("unknown setting {}, expected one of {}", key, KEYS.join(", ")))),
+        }
+    }
+
+    /// Checks a new habit name against the name settings.
+    pub fn validate_name(&self, name: &str) -> Result<(), CliError> {
+
+        if self.names_ascii_only && !name.is_ascii() {
+            return Err(CliError(format!("habit name {} is not ASCII, names.ascii_only is set", name)));
+        }
+
+        Ok(())
+    }
+}
+
+pub const WEEK_STARTS: [&str; 2] = ["mon", "sun"];
+
+pub fn parse_week_start(value: &str) -> Result<Weekday, CliError> {
+    match value {
+        "mon" => Ok(Weekday::Mon),
+        "sun" => Ok(Weekday::Sun),
+        _ => Err(CliError(format!("invalid first day of week {}, expected one of {}", value, WEEK_STARTS.join(", ")))),
+    }
+}
+
+fn week_start_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Sun => "sun",
+        _ => "mon",
+    }
+}
+
+/// `None` for an empty value, which turns the setting off.
+fn optional(value: &str) -> Option<String> {
+    Some(value.trim().to_owned()).filter(|value| !value.is_empty())
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, CliError> {
+    match value {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(CliError(format!("invalid value {} for {}, expected true or false", value, key))),
+    }
+}
@@ -1,8 +1,47 @@
-use chrono::{DateTime, Datelike, Duration, Local};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, TimeZone, Utc, Weekday};
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::error::CliError;
 
-#[derive(Debug, PartialEq)]
+/// Timezone used to decide which day "today" is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+    Local,
+    Utc,
+    Offset(FixedOffset),
+}
+
+impl Zone {
+    pub fn parse(zone: &str) -> Result<Zone, CliError> {
+
+        let zone = zone.trim();
+
+        if zone.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        }
+        if zone.eq_ignore_ascii_case("utc") || zone == "Z" {
+            return Ok(Zone::Utc);
+        }
+
+        match zone.parse::<FixedOffset>() {
+            Ok(offset) => Ok(Zone::Offset(offset)),
+            Err(_) => Err(CliError(format!("invalid timezone {}, expected utc, local or an offset like +02:00", zone))),
+        }
+    }
+
+    /// The calendar date in this zone at the instant `now`.
+    pub fn date_at(&self, now: DateTime<Utc>) -> Date {
+        match self {
+            Zone::Local => datetime_to_date(now.with_timezone(&Local)),
+            Zone::Utc => datetime_to_date(now),
+            Zone::Offset(offset) => datetime_to_date(now.with_timezone(offset)),
+        }
+    }
+}
+
+/// Orders chronologically, by year, month and day.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Date {
     pub year: i32,
     pub month: i32,
@@ -25,10 +64,11 @@ impl Date {
         if y_str.len() != 4 {
             return Err(CliError(format!("failed to parse year {}, expected YYYY", y_str)));
         }
-        if m_str.len() != 2 {
+        // single digit month and day are unambiguous, accept them too
+        if m_str.is_empty() || m_str.len() > 2 || !m_str.bytes().all(|b| b.is_ascii_digit()) {
             return Err(CliError(format!("failed to parse month {}, expected MM", m_str)));
         }
-        if d_str.len() != 2 {
+        if d_str.is_empty() || d_str.len() > 2 || !d_str.bytes().all(|b| b.is_ascii_digit()) {
             return Err(CliError(format!("failed to parse day {}, expected DD", d_str)));
         }
 
@@ -45,7 +85,49 @@ impl Date {
         if result.is_valid() {
             Ok(result)
         } else {
-            return Err(CliError(format!("invalid date {}", date)));
+            Err(CliError(format!("invalid date {}", date)))
+        }
+    }
+
+    /// Year and month of a `YYYY-MM` string, the month may be a single digit.
+    pub fn parse_month(month: &str) -> Result<(i32, i32), CliError> {
+
+        let (y_str, m_str) = match month.trim().split_once('-') {
+            Some(parts) => parts,
+            None => return Err(CliError(format!("failed to parse month {}, expected YYYY-MM format", month))),
+        };
+
+        if y_str.len() != 4 || !y_str.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(CliError(format!("failed to parse year {}, expected YYYY", y_str)));
+        }
+        if m_str.is_empty() || m_str.len() > 2 || !m_str.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(CliError(format!("failed to parse month {}, expected MM", m_str)));
+        }
+
+        let y = y_str.parse::<i32>()?;
+        let m = m_str.parse::<i32>()?;
+
+        if y < 1 || !(1..=12).contains(&m) {
+            return Err(CliError(format!("invalid month {}", month)));
+        }
+
+        Ok((y, m))
+    }
+
+    /// The 1st of the month, checked like any other date.
+    pub fn first_of_month(year: i32, month: i32) -> Result<Date, CliError> {
+        Date::checked(Date { year, month, day: 1 })
+    }
+
+    /// The last day of the month, the 29th for February in leap years.
+    pub fn last_of_month(year: i32, month: i32) -> Result<Date, CliError> {
+        Date::checked(Date { year, month, day: num_days(year, month) })
+    }
+
+    fn checked(date: Date) -> Result<Date, CliError> {
+        match date.is_valid() {
+            true => Ok(date),
+            false => Err(CliError(format!("invalid month {:04}-{:02}", date.year, date.month))),
         }
     }
 
@@ -58,7 +140,7 @@ impl Date {
             return false;
         }
 
-        if m < 1 || m > 12 {
+        if !(1..=12).contains(&m) {
             return false;
         }
 
@@ -81,20 +163,143 @@ impl Date {
         Ok(result)
     }
 
-    pub fn today() -> Date {
-        let local = Local::now();
-        datetime_to_date(local)
+    pub fn to_naive(&self) -> Result<NaiveDate, CliError> {
+
+        match NaiveDate::from_ymd_opt(self.year, self.month as u32, self.day as u32) {
+            Some(date) => Ok(date),
+            None => Err(CliError(format!("invalid date {:04}-{:02}-{:02}", self.year, self.month, self.day))),
+        }
+    }
+
+    /// Date `n` days later, negative `n` goes back.
+    /// Panics if the date is invalid.
+    pub fn add_days(&self, n: i64) -> Date {
+        let date = self.to_naive().expect("invalid date");
+        Date::from_naive(date + Duration::days(n))
+    }
+
+    /// Date `n` days earlier.
+    /// Panics if the date is invalid.
+    pub fn sub_days(&self, n: i64) -> Date {
+        self.add_days(-n)
+    }
+
+    pub fn day_of_week(&self) -> Result<Weekday, CliError> {
+        Ok(self.to_naive()?.weekday())
+    }
+
+    /// Day of the year, 1 for January 1st up to 366.
+    pub fn ordinal(&self) -> Result<u32, CliError> {
+        Ok(self.to_naive()?.ordinal())
+    }
+
+    /// Monday to Sunday week of the year, starting at 1 for the week with
+    /// January 1st even if it is partial. This is the column of a year view.
+    pub fn week_of_year(&self) -> Result<u32, CliError> {
+
+        let january = Date { year: self.year, month: 1, day: 1 };
+        let offset = january.day_of_week()?.num_days_from_monday();
+
+        Ok((self.ordinal()? - 1 + offset) / 7 + 1)
+    }
+
+    pub fn is_weekend(&self) -> Result<bool, CliError> {
+
+        let weekday = self.day_of_week()?;
+        Ok(weekday == Weekday::Sat || weekday == Weekday::Sun)
+    }
+
+    pub fn from_naive(date: NaiveDate) -> Date {
+        Date {
+            year: date.year(),
+            month: date.month() as i32,
+            day: date.day() as i32,
+        }
     }
 
-    pub fn yesterday() -> Date {
-        let local = Local::now() - Duration::days(1);
-        datetime_to_date(local)
+}
+
+/// Serializes as the ISO `YYYY-MM-DD` string.
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+
+        let date = self.to_string().map_err(ser::Error::custom)?;
+        serializer.serialize_str(&date)
+    }
+}
+
+/// Reads an ISO date string, rejecting invalid dates.
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Date, D::Error> {
+
+        let date = String::deserialize(deserializer)?;
+        Date::from_string(&date).map_err(de::Error::custom)
     }
+}
 
+/// Which days of a range an operation applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DayFilter {
+    All,
+    Weekends,
+}
 
+impl DayFilter {
+    pub fn matches(&self, date: &Date) -> Result<bool, CliError> {
+        match self {
+            DayFilter::All => Ok(true),
+            DayFilter::Weekends => date.is_weekend(),
+        }
+    }
 }
 
-fn datetime_to_date(local: DateTime<Local>) -> Date {
+/// Iterator over the dates from `start` to `end`, inclusive. Empty if `end`
+/// is before `start`. Panics if either date is invalid.
+pub struct DateRange {
+    next: Date,
+    end: Date,
+}
+
+impl DateRange {
+    pub fn new(start: &Date, end: &Date) -> DateRange {
+        DateRange {
+            next: start.clone(),
+            end: end.clone(),
+        }
+    }
+}
+
+impl Iterator for DateRange {
+    type Item = Date;
+
+    fn next(&mut self) -> Option<Date> {
+
+        if self.next > self.end {
+            return None;
+        }
+
+        let following = self.next.add_days(1);
+        Some(std::mem::replace(&mut self.next, following))
+    }
+}
+
+/// Number of days from `start` to `end`, inclusive. 0 if `end` is before `start`.
+pub fn count_days(start: &Date, end: &Date) -> Result<u32, CliError> {
+
+    let days = (end.to_naive()? - start.to_naive()?).num_days() + 1;
+    Ok(days.max(0) as u32)
+}
+
+/// Every date from `start` to `end`, inclusive. Empty if `end` is before `start`.
+pub fn days_between(start: &Date, end: &Date) -> Result<Vec<Date>, CliError> {
+
+    start.to_naive()?;
+    end.to_naive()?;
+
+    Ok(DateRange::new(start, end).collect())
+}
+
+fn datetime_to_date<Tz: TimeZone>(local: DateTime<Tz>) -> Date {
     let year = local.year();
     let month = local.month() as i32;
     let day = local.day() as i32;
@@ -125,4 +330,197 @@ pub fn num_days(year: i32, month: i32) -> i32  {
         12 => 31,
         _ => 0
     }
-}
\ No newline at end of file
+}
+
+/// All (year, month) pairs from the month of `start` to the month of `end`, inclusive.
+pub fn month_span(start: &Date, end: &Date) -> Vec<(i32, i32)> {
+
+    let mut result = vec![];
+
+    let mut year = start.year;
+    let mut month = start.month;
+
+    while (year, month) <= (end.year, end.month) {
+        result.push((year, month));
+
+        month += 1;
+        if month > 12 {
+            month = 1;
+            year += 1;
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_month_bounds() {
+        assert_eq!(Date::first_of_month(2024, 2).unwrap(), Date { year: 2024, month: 2, day: 1 });
+        assert_eq!(Date::last_of_month(2024, 2).unwrap(), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(Date::last_of_month(2023, 2).unwrap(), Date { year: 2023, month: 2, day: 28 });
+        assert_eq!(Date::last_of_month(1900, 2).unwrap(), Date { year: 1900, month: 2, day: 28 });
+        assert_eq!(Date::last_of_month(2000, 2).unwrap(), Date { year: 2000, month: 2, day: 29 });
+        assert_eq!(Date::last_of_month(2024, 12).unwrap(), Date { year: 2024, month: 12, day: 31 });
+
+        assert!(Date::first_of_month(2024, 13).is_err());
+        assert!(Date::last_of_month(2024, 0).is_err());
+        assert!(Date::first_of_month(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_month() {
+        assert_eq!(Date::parse_month("2024-03").unwrap(), (2024, 3));
+        assert_eq!(Date::parse_month("2024-3").unwrap(), (2024, 3));
+        assert_eq!(Date::parse_month("2024-12").unwrap(), (2024, 12));
+
+        assert!(Date::parse_month("2024-13").is_err());
+        assert!(Date::parse_month("2024-00").is_err());
+        assert!(Date::parse_month("2024").is_err());
+        assert!(Date::parse_month("2024-03-01").is_err());
+        assert!(Date::parse_month("24-03").is_err());
+    }
+
+    #[test]
+    fn test_ordinal() {
+        assert_eq!(Date { year: 2024, month: 1, day: 1 }.ordinal().unwrap(), 1);
+        assert_eq!(Date { year: 2024, month: 12, day: 31 }.ordinal().unwrap(), 366);
+        assert_eq!(Date { year: 2023, month: 12, day: 31 }.ordinal().unwrap(), 365);
+        assert_eq!(Date { year: 2023, month: 7, day: 1 }.ordinal().unwrap(), 182);
+        assert!(Date { year: 2023, month: 2, day: 29 }.ordinal().is_err());
+    }
+
+    #[test]
+    fn test_week_of_year() {
+        // 2024 starts on a Monday, 2023 on a Sunday
+        assert_eq!(Date { year: 2024, month: 1, day: 1 }.week_of_year().unwrap(), 1);
+        assert_eq!(Date { year: 2024, month: 1, day: 7 }.week_of_year().unwrap(), 1);
+        assert_eq!(Date { year: 2024, month: 1, day: 8 }.week_of_year().unwrap(), 2);
+        assert_eq!(Date { year: 2024, month: 12, day: 31 }.week_of_year().unwrap(), 53);
+        assert_eq!(Date { year: 2023, month: 1, day: 1 }.week_of_year().unwrap(), 1);
+        assert_eq!(Date { year: 2023, month: 1, day: 2 }.week_of_year().unwrap(), 2);
+        assert_eq!(Date { year: 2023, month: 12, day: 31 }.week_of_year().unwrap(), 53);
+    }
+
+    #[test]
+    fn test_from_string_single_digit() {
+        assert_eq!(Date::from_string("2024-3-7").unwrap(), Date { year: 2024, month: 3, day: 7 });
+        assert_eq!(Date::from_string("2024-03-7").unwrap(), Date { year: 2024, month: 3, day: 7 });
+        assert_eq!(Date::from_string("2024-3-7").unwrap().to_string().unwrap(), "2024-03-07");
+
+        assert!(Date::from_string("2024-003-07").is_err());
+        assert!(Date::from_string("2024--07").is_err());
+        assert!(Date::from_string("24-3-7").is_err());
+        assert!(Date::from_string("2024-+3-7").is_err());
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let date = Date { year: 2024, month: 2, day: 29 };
+        let json = serde_json::to_string(&date).unwrap();
+
+        assert_eq!(json, "\"2024-02-29\"");
+        assert_eq!(serde_json::from_str::<Date>(&json).unwrap(), date);
+        assert_eq!(serde_json::from_str::<Vec<Date>>("[\"2024-3-7\"]").unwrap(), vec![Date { year: 2024, month: 3, day: 7 }]);
+
+        assert!(serde_json::from_str::<Date>("\"2023-02-29\"").is_err());
+        assert!(serde_json::from_str::<Date>("\"2024-02-2x\"").is_err());
+        assert!(serde_json::from_str::<Date>("20240229").is_err());
+        assert!(serde_json::to_string(&Date { year: 2023, month: 2, day: 29 }).is_err());
+    }
+
+    #[test]
+    fn test_month_span() {
+        let start = Date { year: 2023, month: 11, day: 30 };
+        let end = Date { year: 2024, month: 2, day: 1 };
+        assert_eq!(month_span(&start, &end), vec![(2023, 11), (2023, 12), (2024, 1), (2024, 2)]);
+
+        let same = Date { year: 2024, month: 5, day: 3 };
+        assert_eq!(month_span(&same, &same), vec![(2024, 5)]);
+
+        assert!(month_span(&end, &start).is_empty());
+    }
+
+    #[test]
+    fn test_zone_date_at() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 23, 30, 0).unwrap();
+
+        assert_eq!(Zone::Utc.date_at(now), Date { year: 2024, month: 3, day: 1 });
+        assert_eq!(Zone::parse("+02:00").unwrap().date_at(now), Date { year: 2024, month: 3, day: 2 });
+        assert_eq!(Zone::parse("-10:00").unwrap().date_at(now), Date { year: 2024, month: 3, day: 1 });
+
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 0, 30, 0).unwrap();
+        assert_eq!(Zone::parse("-01:00").unwrap().date_at(now), Date { year: 2024, month: 2, day: 29 });
+    }
+
+    #[test]
+    fn test_zone_parse() {
+        assert_eq!(Zone::parse("UTC").unwrap(), Zone::Utc);
+        assert_eq!(Zone::parse("local").unwrap(), Zone::Local);
+        assert!(Zone::parse("Europe/Berlin").is_err());
+    }
+
+    #[test]
+    fn test_add_days() {
+        let date = Date { year: 2023, month: 12, day: 31 };
+        assert_eq!(date.add_days(1), Date { year: 2024, month: 1, day: 1 });
+        assert_eq!(date.add_days(60), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(date.add_days(0), date);
+
+        let leap = Date { year: 2024, month: 2, day: 28 };
+        assert_eq!(leap.add_days(1), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(leap.add_days(2), Date { year: 2024, month: 3, day: 1 });
+
+        let non_leap = Date { year: 2023, month: 2, day: 28 };
+        assert_eq!(non_leap.add_days(1), Date { year: 2023, month: 3, day: 1 });
+    }
+
+    #[test]
+    fn test_sub_days() {
+        let date = Date { year: 2024, month: 1, day: 1 };
+        assert_eq!(date.sub_days(1), Date { year: 2023, month: 12, day: 31 });
+        assert_eq!(date.sub_days(-1), Date { year: 2024, month: 1, day: 2 });
+
+        let march = Date { year: 2024, month: 3, day: 1 };
+        assert_eq!(march.sub_days(1), Date { year: 2024, month: 2, day: 29 });
+
+        let march = Date { year: 2100, month: 3, day: 1 };
+        assert_eq!(march.sub_days(1), Date { year: 2100, month: 2, day: 28 });
+    }
+
+    #[test]
+    fn test_date_range() {
+        let start = Date { year: 2024, month: 1, day: 30 };
+        let end = Date { year: 2024, month: 2, day: 2 };
+
+        let days = DateRange::new(&start, &end).collect::<Vec<Date>>();
+        assert_eq!(days, vec![
+            Date { year: 2024, month: 1, day: 30 },
+            Date { year: 2024, month: 1, day: 31 },
+            Date { year: 2024, month: 2, day: 1 },
+            Date { year: 2024, month: 2, day: 2 },
+        ]);
+
+        assert_eq!(DateRange::new(&start, &start).collect::<Vec<Date>>(), vec![start.clone()]);
+        assert_eq!(DateRange::new(&end, &start).count(), 0);
+    }
+
+    #[test]
+    fn test_days_between() {
+        let start = Date { year: 2023, month: 12, day: 30 };
+        let end = Date { year: 2024, month: 1, day: 2 };
+        let days = days_between(&start, &end).unwrap();
+        assert_eq!(days.len(), 4);
+        assert_eq!(days[0], start);
+        assert_eq!(days[3], end);
+
+        assert!(days_between(&end, &start).unwrap().is_empty());
+
+        assert_eq!(count_days(&start, &end).unwrap(), 4);
+        assert_eq!(count_days(&start, &start).unwrap(), 1);
+        assert_eq!(count_days(&end, &start).unwrap(), 0);
+    }
+}
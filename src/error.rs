@@ -27,6 +27,15 @@ impl std::error::Error for CliError {}
 
 impl From<rusqlite::Error> for CliError {
     fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::ReadOnly) => CliError::new("database is opened read-only, run without --readonly to change it"),
+            _ => CliError(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for CliError {
+    fn from(err: serde_json::Error) -> Self {
         CliError(err.to_string())
     }
 }
@@ -0,0 +1,382 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{date::Date, error::CliError, storage::{Entry, HabitExport}};
+
+pub const JSON_VERSION: u64 = 1;
+
+/// The JSON export as written.
+#[derive(Serialize)]
+struct ExportFile<'a> {
+    version: u64,
+    habits: &'a [HabitExport],
+}
+
+/// The JSON export as read, files without a version are taken as the current one.
+#[derive(Deserialize)]
+struct ImportFile {
+    #[serde(default)]
+    version: u64,
+    habits: Vec<HabitExport>,
+}
+
+pub fn to_json(habits: &[HabitExport]) -> Result<String, CliError> {
+    Ok(serde_json::to_string(&ExportFile { version: JSON_VERSION, habits })?)
+}
+
+pub fn from_json(text: &str) -> Result<Vec<HabitExport>, CliError> {
+
+    let file: ImportFile = match serde_json::from_str(text) {
+        Ok(file) => file,
+        Err(err) => return Err(CliError(format!("invalid export, {}", err))),
+    };
+
+    if file.version > JSON_VERSION {
+        return Err(CliError(format!("unsupported export version {}", file.version)));
+    }
+
+    Ok(file.habits)
+}
+
+/// One `habit,date` line per entry, with a header line.
+pub fn to_csv(entries: &[Entry]) -> Result<String, CliError> {
+    Ok(to_delimited(&["habit", "date"], &entry_rows(entries)?, ',', escape_csv))
+}
+
+/// Like `to_csv`, separated by tabs.
+pub fn to_tsv(entries: &[Entry]) -> Result<String, CliError> {
+    Ok(to_delimited(&["habit", "date"], &entry_rows(entries)?, '\t', escape_tsv))
+}
+
+/// One `id,name,color` line per habit, without entries.
+pub fn habits_to_csv(habits: &[HabitExport]) -> String {
+    to_delimited(&["id", "name", "color"], &habit_rows(habits), ',', escape_csv)
+}
+
+/// Like `habits_to_csv`, separated by tabs.
+pub fn habits_to_tsv(habits: &[HabitExport]) -> String {
+    to_delimited(&["id", "name", "color"], &habit_rows(habits), '\t', escape_tsv)
+}
+
+/// Formats `Storage::stream_export` can write while reading rows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StreamFormat {
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl StreamFormat {
+
+    /// `None` for formats that are only written in one piece.
+    pub fn parse(format: &str) -> Option<StreamFormat> {
+        match format {
+            "json" => Some(StreamFormat::Json),
+            "csv" => Some(StreamFormat::Csv),
+            "tsv" => Some(StreamFormat::Tsv),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the same output as `to_json`, `to_csv` or `to_tsv`, one row at a time.
+/// Rows of one habit must come one after another.
+pub struct ExportStream<'a, W: Write> {
+    w: &'a mut W,
+    format: StreamFormat,
+    habit: Option<String>,
+    entries: usize,
+}
+
+impl<'a, W: Write> ExportStream<'a, W> {
+
+    pub fn begin(w: &'a mut W, format: StreamFormat) -> Result<Self, CliError> {
+
+        let mut stream = ExportStream { w, format, habit: None, entries: 0 };
+        match format {
+            StreamFormat::Json => stream.write(&format!("{{\"version\":{},\"habits\":[", JSON_VERSION))?,
+            StreamFormat::Csv => stream.write("habit,date\n")?,
+            StreamFormat::Tsv => stream.write("habit\tdate\n")?,
+        }
+
+        Ok(stream)
+    }
+
+    /// Adds one entry of a habit, or just the habit when `date` is `None`.
+    pub fn row(&mut self, id: &str, name: &str, color: Option<&str>, date: Option<&Date>) -> Result<(), CliError> {
+
+        match self.format {
+            StreamFormat::Json => {
+                if self.habit.as_deref() != Some(id) {
+                    let separator = match self.habit {
+                        Some(_) => "]},",
+                        None => "",
+                    };
+                    self.write(&format!("{}{{\"id\":{},\"name\":{},\"color\":{},\"entries\":[", separator,
+                        serde_json::to_string(id)?, serde_json::to_string(name)?, serde_json::to_string(&color)?))?;
+                    self.habit = Some(id.to_owned());
+                    self.entries = 0;
+                }
+                if let Some(date) = date {
+                    let separator = if self.entries > 0 { "," } else { "" };
+                    self.write(&format!("{}{}", separator, serde_json::to_string(date)?))?;
+                    self.entries += 1;
+                }
+            },
+            StreamFormat::Csv | StreamFormat::Tsv => {
+                if let Some(date) = date {
+                    let (separator, escape): (&str, fn(&str) -> String) = match self.format {
+                        StreamFormat::Tsv => ("\t", escape_tsv),
+                        _ => (",", escape_csv),
+                    };
+                    self.write(&format!("{}{}{}\n", escape(name), separator, date.to_string()?))?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), CliError> {
+
+        if self.format == StreamFormat::Json {
+            let end = match self.habit {
+                Some(_) => "]}]}\n",
+                None => "]}\n",
+            };
+            self.write(end)?;
+        }
+
+        if let Err(err) = self.w.flush() {
+            return Err(CliError(format!("failed to write export: {}", err)));
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), CliError> {
+        match self.w.write_all(text.as_bytes()) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(CliError(format!("failed to write export: {}", err))),
+        }
+    }
+}
+
+/// Reads `habit,date` rows as written by `to_csv`. The header line is optional.
+pub fn from_csv(text: &str) -> Result<Vec<(String, Date)>, CliError> {
+
+    let mut result = vec![];
+    for (i, row) in parse_csv(text)?.into_iter().enumerate() {
+        if i == 0 && row == ["habit", "date"] {
+            continue;
+        }
+
+        match row.as_slice() {
+            [name, date] => result.push((name.clone(), Date::from_string(date)?)),
+            _ => return Err(CliError(format!("invalid csv row {}, expected habit,date", i + 1))),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Splits CSV text into rows of fields. Quoted fields may contain commas,
+/// line breaks and doubled quotes. Empty lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, CliError> {
+
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {},
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                if row != [""] {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            },
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(CliError::new("invalid csv, unterminated quote"));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+fn entry_rows(entries: &[Entry]) -> Result<Vec<Vec<String>>, CliError> {
+
+    let mut rows = Vec::with_capacity(entries.len());
+    for entry in entries {
+        rows.push(vec![entry.name.clone(), entry.date.to_string()?]);
+    }
+
+    Ok(rows)
+}
+
+fn habit_rows(habits: &[HabitExport]) -> Vec<Vec<String>> {
+    habits.iter()
+        .map(|habit| vec![habit.id.clone(), habit.name.clone(), habit.color.clone().unwrap_or_default()])
+        .collect()
+}
+
+fn to_delimited(header: &[&str], rows: &[Vec<String>], separator: char, escape: fn(&str) -> String) -> String {
+
+    let separator = separator.to_string();
+
+    let mut result = header.join(&separator);
+    result.push('\n');
+    for row in rows {
+        let fields = row.iter().map(|field| escape(field)).collect::<Vec<String>>();
+        result.push_str(&fields.join(&separator));
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Quotes the field if it contains a comma, quote or line break.
+fn escape_csv(field: &str) -> String {
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// TSV has no quoting, tabs and line breaks are written as `\t`, `\n` and `\r`.
+fn escape_tsv(field: &str) -> String {
+    field.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_csv() {
+        let text = "habit,date\nread,2024-03-01\r\n\n\"walk, run\",2024-03-02\n\"say \"\"hi\"\"\",2024-03-03";
+        let day = |day| Date { year: 2024, month: 3, day };
+
+        assert_eq!(from_csv(text).unwrap(), vec![
+            ("read".to_owned(), day(1)),
+            ("walk, run".to_owned(), day(2)),
+            ("say \"hi\"".to_owned(), day(3)),
+        ]);
+        assert_eq!(from_csv("read,2024-03-01\n").unwrap(), vec![("read".to_owned(), day(1))]);
+
+        assert!(from_csv("read,2024-03-01,x\n").is_err());
+        assert!(from_csv("read,2024-13-01\n").is_err());
+        assert!(from_csv("\"read,2024-03-01\n").is_err());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let habits = vec![
+            HabitExport {
+                id: "hbt_1".to_owned(),
+                name: "read".to_owned(),
+                color: Some("green".to_owned()),
+                entries: vec![Date { year: 2024, month: 3, day: 1 }, Date { year: 2024, month: 3, day: 2 }],
+            },
+            HabitExport {
+                id: "hbt_2".to_owned(),
+                name: "gym".to_owned(),
+                color: None,
+                entries: vec![],
+            },
+        ];
+
+        let text = to_json(&habits).unwrap();
+        assert!(text.starts_with(r#"{"version":1,"habits":[{"id":"#));
+        let parsed = from_json(&text).unwrap();
+        assert_eq!(parsed, habits);
+    }
+
+    #[test]
+    fn test_from_json_invalid_date() {
+        assert!(from_json(r#"{"version":1,"habits":[{"id":"a","name":"b","entries":["2024-02-30"]}]}"#).is_err());
+        assert!(from_json(r#"{"version":2,"habits":[]}"#).is_err());
+        assert_eq!(from_json(r#"{"habits":[{"id":"a","name":"b"}]}"#).unwrap()[0].entries, vec![]);
+    }
+
+    fn entry(name: &str, day: i32) -> Entry {
+        Entry {
+            habit_id: "hbt_1".to_owned(),
+            name: name.to_owned(),
+            color: None,
+            date: Date { year: 2024, month: 3, day },
+        }
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let entries = vec![entry("read", 1), entry("a, \"b\"", 2)];
+        assert_eq!(to_csv(&entries).unwrap(), "habit,date\nread,2024-03-01\n\"a, \"\"b\"\"\",2024-03-02\n");
+    }
+
+    #[test]
+    fn test_habits_to_csv() {
+        let habits = vec![
+            HabitExport { id: "hbt_1".to_owned(), name: "read".to_owned(), color: Some("red".to_owned()), entries: vec![] },
+            HabitExport { id: "hbt_2".to_owned(), name: "a,b".to_owned(), color: None, entries: vec![] },
+        ];
+        assert_eq!(habits_to_csv(&habits), "id,name,color\nhbt_1,read,red\nhbt_2,\"a,b\",\n");
+        assert_eq!(habits_to_tsv(&habits), "id\tname\tcolor\nhbt_1\tread\tred\nhbt_2\ta,b\t\n");
+    }
+
+    #[test]
+    fn test_export_stream() {
+        let day = |day| Date { year: 2024, month: 3, day };
+        let rows = [("hbt_1", "read", Some("green"), Some(day(1))), ("hbt_1", "read", Some("green"), Some(day(2))), ("hbt_2", "a,b", None, None)];
+
+        let stream = |format| {
+            let mut out = vec![];
+            let mut stream = ExportStream::begin(&mut out, format).unwrap();
+            for (id, name, color, date) in &rows {
+                stream.row(id, name, *color, date.as_ref()).unwrap();
+            }
+            stream.finish().unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let habits = vec![
+            HabitExport { id: "hbt_1".to_owned(), name: "read".to_owned(), color: Some("green".to_owned()), entries: vec![day(1), day(2)] },
+            HabitExport { id: "hbt_2".to_owned(), name: "a,b".to_owned(), color: None, entries: vec![] },
+        ];
+        assert_eq!(stream(StreamFormat::Json), format!("{}\n", to_json(&habits).unwrap()));
+        assert_eq!(stream(StreamFormat::Csv), "habit,date\nread,2024-03-01\nread,2024-03-02\n");
+
+        let mut out = vec![];
+        ExportStream::begin(&mut out, StreamFormat::Json).unwrap().finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", to_json(&[]).unwrap()));
+    }
+
+    #[test]
+    fn test_to_tsv() {
+        let entries = vec![entry("read", 1), entry("a\tb\\c", 2)];
+        assert_eq!(to_tsv(&entries).unwrap(), "habit\tdate\nread\t2024-03-01\na\\tb\\\\c\t2024-03-02\n");
+    }
+}
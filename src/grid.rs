@@ -0,0 +1,701 @@
+use std::ops::{Range, RangeInclusive};
+
+use chrono::Weekday;
+
+use crate::{color::{self, Palette}, date::{self, Date}, locale::Locale};
+
+pub struct GridRow {
+    pub name: String,
+    pub color: Option<String>,
+    /// Marked state per day of the month, index 0 is the 1st.
+    pub marked: Vec<bool>,
+    pub paused: bool,
+    /// Days per week the habit should be marked.
+    pub weekly_goal: Option<u32>,
+}
+
+impl GridRow {
+    fn label(&self) -> String {
+        match self.paused {
+            true => format!("{} (paused)", self.name),
+            false => self.name.clone(),
+        }
+    }
+}
+
+/// One month of marks for a set of habits, rendered by `list`.
+pub struct MonthGrid {
+    pub year: i32,
+    pub month: i32,
+    pub rows: Vec<GridRow>,
+    /// Fixed width of the name column, longer names are truncated.
+    /// Sized to the longest name if `None`.
+    pub name_width: Option<usize>,
+    /// Today, set to show unmarked days before it as missed.
+    pub fill: Option<Date>,
+    /// Add a column per week showing whether the weekly goal was met.
+    pub weekly: bool,
+    /// Today, set to accent its column when colors are used.
+    pub highlight: Option<Date>,
+    /// Write day numbers in two header rows, tens above units.
+    pub two_row_header: bool,
+    /// Colors for marks and missed days when colors are used.
+    pub palette: Palette,
+    /// First and last day of the month to show, inclusive. The whole month if `None`,
+    /// the rows only hold these days.
+    pub window: Option<(i32, i32)>,
+}
+
+impl MonthGrid {
+
+    pub fn num_days(&self) -> i32 {
+        date::num_days(self.year, self.month)
+    }
+
+    /// Days of the month shown as columns.
+    fn days(&self) -> RangeInclusive<i32> {
+        match self.window {
+            Some((first, last)) => first..=last,
+            None => 1..=self.num_days(),
+        }
+    }
+
+    /// Day of the month of a column index.
+    fn day_at(&self, index: usize) -> i32 {
+        self.days().start() + index as i32
+    }
+
+    fn month_display(&self) -> String {
+        format!("{:04}-{:02}", self.year, self.month)
+    }
+
+    fn name_width(&self) -> usize {
+
+        if let Some(width) = self.name_width {
+            // the month header has to fit
+            return width.max(self.month_display().len());
+        }
+
+        let mut target_indent = self.month_display().len() + 2;
+        for row in &self.rows {
+            let len = row.label().chars().count();
+            if len > target_indent {
+                target_indent = len;
+            }
+        }
+
+        target_indent
+    }
+
+    fn push_header(&self, result: &mut String, target_indent: usize) {
+
+        let month_display = self.month_display();
+
+        result.push_str(&month_display);
+        result.push_str(&str::repeat(" ", target_indent - month_display.len()));
+        result.push_str("| ");
+
+        if self.two_row_header {
+            // tens on the first row, units below
+            for i in self.days() {
+                result.push(if i < 10 { ' ' } else { char::from(b'0' + (i / 10) as u8) });
+            }
+            if self.weekly {
+                result.push_str(" | ");
+            }
+            let len = result.trim_end().len();
+            result.truncate(len);
+            result.push('\n');
+            result.push_str(&str::repeat(" ", target_indent));
+            result.push_str("| ");
+        }
+
+        for i in self.days() {
+            result.push_str(&format!("{}", i % 10));
+        }
+        if self.weekly {
+            result.push_str(" | ");
+            for i in 1..self.weeks().len()+1 {
+                result.push_str(&format!("{}", i % 10));
+            }
+        }
+        result.push('\n');
+    }
+
+    /// Day indexes of each Monday to Sunday week, the first and last may be partial.
+    fn weeks(&self) -> Vec<Range<usize>> {
+
+        let week = |day: i32| Date { year: self.year, month: self.month, day }.week_of_year().unwrap_or(0);
+
+        let mut result: Vec<Range<usize>> = vec![];
+        for (i, day) in self.days().enumerate() {
+            match result.last_mut() {
+                Some(last) if i > 0 && week(day) == week(day - 1) => last.end += 1,
+                _ => result.push(i..i + 1),
+            }
+        }
+
+        result
+    }
+
+    /// ✓ or ✗ per week, partial weeks need a proportional share of the goal.
+    fn push_weekly(&self, result: &mut String, row: &GridRow) {
+
+        result.push_str(" | ");
+        for week in self.weeks() {
+            let goal = match row.weekly_goal {
+                Some(goal) => (goal as usize * week.len()).div_ceil(7),
+                None => {
+                    result.push(' ');
+                    continue;
+                },
+            };
+
+            let count = row.marked[week].iter().filter(|m| **m).count();
+            result.push(if count >= goal { '✓' } else { '✗' });
+        }
+    }
+
+    /// Number of shown days at the start of the month that are over.
+    fn past_days(&self) -> usize {
+
+        let days = self.days();
+        match &self.fill {
+            Some(today) if (self.year, self.month) < (today.year, today.month) => days.count(),
+            Some(today) if (self.year, self.month) == (today.year, today.month) => {
+                (today.day - days.start()).clamp(0, days.count() as i32) as usize
+            },
+            _ => 0,
+        }
+    }
+
+    fn is_weekend(&self, index: usize) -> bool {
+
+        let day = Date { year: self.year, month: self.month, day: self.day_at(index) };
+        matches!(day.day_of_week(), Ok(Weekday::Sat | Weekday::Sun))
+    }
+
+    /// Day index of the highlighted day if it is in this month.
+    fn highlight_column(&self) -> Option<usize> {
+
+        match &self.highlight {
+            Some(today) if (today.year, today.month) == (self.year, self.month) && self.days().contains(&today.day) => {
+                Some((today.day - self.days().start()) as usize)
+            },
+            _ => None,
+        }
+    }
+
+    fn push_row(&self, result: &mut String, row: &GridRow, target_indent: usize, use_color: bool) {
+
+        let mark = match (use_color, &row.color, self.palette.marked) {
+            (true, Some(c), _) => color::paint("X", c),
+            (true, None, Some(code)) => color::paint_code("X", code),
+            _ => "X".to_owned(),
+        };
+
+        let name = truncate(&row.label(), target_indent);
+        result.push_str(&name);
+        result.push_str(&str::repeat(" ", target_indent - name.chars().count()));
+        result.push_str("| ");
+
+        let past_days = self.past_days();
+        let highlight = self.highlight_column().filter(|_| use_color);
+        for (i, marked) in row.marked.iter().enumerate() {
+            let cell = match marked {
+                true => mark.clone(),
+                false if i < past_days => match self.palette.missed(self.is_weekend(i)).filter(|_| use_color) {
+                    Some(code) => color::paint_code("·", code),
+                    None => "·".to_owned(),
+                },
+                false => " ".to_owned(),
+            };
+            match highlight == Some(i) {
+                true => result.push_str(&color::accent(&cell)),
+                false => result.push_str(&cell),
+            }
+        }
+        if self.weekly {
+            self.push_weekly(result, row);
+        }
+        result.push('\n');
+    }
+
+    pub fn render_text(&self, use_color: bool) -> String {
+
+        let target_indent = self.name_width();
+
+        let mut result = String::new();
+        self.push_header(&mut result, target_indent);
+
+        for row in &self.rows {
+            self.push_row(&mut result, row, target_indent, use_color);
+        }
+
+        result
+    }
+
+    /// Like `render_text`, with rows listed under labeled groups of habit names.
+    /// A habit can appear in more than one group.
+    pub fn render_text_grouped(&self, groups: &[(String, Vec<String>)], use_color: bool) -> String {
+
+        let target_indent = self.name_width();
+
+        let mut result = String::new();
+        self.push_header(&mut result, target_indent);
+
+        for (label, names) in groups {
+            let rows = self.rows.iter()
+                .filter(|row| names.contains(&row.name))
+                .collect::<Vec<&GridRow>>();
+
+            if rows.is_empty() {
+                continue;
+            }
+
+            result.push('\n');
+            result.push_str(&format!("[{}]\n", label));
+            for row in rows {
+                self.push_row(&mut result, row, target_indent, use_color);
+            }
+        }
+
+        result
+    }
+
+    /// One line per habit listing the marked day numbers, like `read: 1,2,5,9`.
+    pub fn render_numbers(&self) -> String {
+
+        let mut result = String::new();
+        for row in &self.rows {
+            let days = row.marked.iter()
+                .enumerate()
+                .filter(|(_, marked)| **marked)
+                .map(|(i, _)| self.day_at(i).to_string())
+                .collect::<Vec<String>>();
+
+            match days.is_empty() {
+                true => result.push_str(&format!("{}: -\n", row.label())),
+                false => result.push_str(&format!("{}: {}\n", row.label(), days.join(","))),
+            }
+        }
+
+        result
+    }
+
+    /// GitHub flavored markdown table with habits as rows and days as columns.
+    pub fn render_markdown(&self) -> String {
+
+        let mut result = String::new();
+        result.push_str(&format!("| {} |", self.month_display()));
+        for i in self.days() {
+            result.push_str(&format!(" {} |", i));
+        }
+        result.push('\n');
+
+        result.push_str("| --- |");
+        for _ in self.days() {
+            result.push_str(" :-: |");
+        }
+        result.push('\n');
+
+        for row in &self.rows {
+            result.push_str(&format!("| {} |", escape_markdown(&row.label())));
+            for marked in &row.marked {
+                match marked {
+                    true => result.push_str(" ✅ |"),
+                    false => result.push_str("  |"),
+                }
+            }
+            result.push('\n');
+        }
+
+        result
+    }
+}
+
+/// One habit's month with a row per week, rendered by `show`.
+pub struct Calendar {
+    pub year: i32,
+    pub month: i32,
+    /// Marked state per day of the month, index 0 is the 1st.
+    pub marked: Vec<bool>,
+    /// Weekday of the first column.
+    pub week_start: Weekday,
+}
+
+impl Calendar {
+
+    /// Empty cells before the 1st in the first week.
+    fn leading_blanks(&self) -> usize {
+
+        let first = Date { year: self.year, month: self.month, day: 1 };
+        let weekday = first.day_of_week().map_or(0, |w| w.num_days_from_monday());
+
+        ((weekday + 7 - self.week_start.num_days_from_monday()) % 7) as usize
+    }
+
+    /// Month and weekday headers and the days of the month, marked days end in `*`.
+    pub fn render(&self, locale: Locale) -> String {
+
+        let mut result = format!("{} {:04}\n", locale.month(self.month), self.year);
+
+        let mut weekday = self.week_start;
+        let mut header = String::new();
+        for _ in 0..7 {
+            header.push_str(&format!(" {:<3}", locale.weekday(weekday)));
+            weekday = weekday.succ();
+        }
+        result.push_str(header.trim_end());
+        result.push('\n');
+
+        let mut line = "    ".repeat(self.leading_blanks());
+        let mut column = self.leading_blanks();
+        for (i, marked) in self.marked.iter().enumerate() {
+            line.push_str(&format!(" {:>2}{}", i + 1, if *marked { '*' } else { ' ' }));
+            column += 1;
+
+            if column == 7 || i + 1 == self.marked.len() {
+                result.push_str(line.trim_end());
+                result.push('\n');
+                line.clear();
+                column = 0;
+            }
+        }
+
+        result
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Shortens `name` to `width` characters, ending in an ellipsis if cut.
+fn truncate(name: &str, width: usize) -> String {
+
+    if name.chars().count() <= width {
+        return name.to_owned();
+    }
+
+    let mut result = name.chars().take(width.saturating_sub(1)).collect::<String>();
+    result.push('…');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> MonthGrid {
+        let mut marked = vec![false; 29];
+        marked[0] = true;
+        marked[28] = true;
+
+        MonthGrid {
+            year: 2024,
+            month: 2,
+            rows: vec![
+                GridRow { name: "read".to_owned(), color: None, marked, paused: false, weekly_goal: None },
+                GridRow { name: "a|b".to_owned(), color: None, marked: vec![false; 29], paused: false, weekly_goal: None },
+            ],
+            name_width: None,
+            fill: None,
+            weekly: false,
+            highlight: None,
+            two_row_header: false,
+            palette: Palette::default(),
+            window: None,
+        }
+    }
+
+    #[test]
+    fn test_name_width() {
+        let mut grid = grid();
+        grid.rows[0].name = "reading books".to_owned();
+        grid.rows[1].name = "läuft täglich".to_owned();
+        grid.name_width = Some(8);
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "2024-02 | 12345678901234567890123456789");
+        assert!(lines[1].starts_with("reading…| X"));
+        assert!(lines[2].starts_with("läuft t…| "));
+
+        grid.name_width = Some(2);
+        assert!(grid.render_text(false).lines().nth(1).unwrap().starts_with("readin…| X"));
+    }
+
+    #[test]
+    fn test_palette() {
+        let mut grid = grid();
+        grid.fill = Some(Date { year: 2024, month: 3, day: 1 });
+        grid.rows[1].color = Some("red".to_owned());
+        grid.rows[1].marked[0] = true;
+
+        let plain = grid.render_text(true);
+        assert!(plain.contains("| X··"));
+
+        grid.palette = Palette { marked: Some("32"), empty: Some("37"), weekend: Some("34") };
+        let text = grid.render_text(true);
+        let lines = text.lines().collect::<Vec<&str>>();
+
+        // february 2024 starts on a thursday, the 3rd is a saturday
+        let x = color::paint_code("X", "32");
+        let empty = color::paint_code("·", "37");
+        let weekend = color::paint_code("·", "34");
+        assert!(lines[1].starts_with(&format!("read     | {}{}{}{}", x, empty, weekend, weekend)));
+        assert!(lines[2].starts_with(&format!("a|b      | {}{}", color::paint("X", "red"), empty)));
+
+        assert!(!grid.render_text(false).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_window() {
+        let mut grid = grid();
+        grid.window = Some((3, 12));
+        grid.rows[0].marked = vec![false; 10];
+        grid.rows[0].marked[0] = true;
+        grid.rows[0].marked[9] = true;
+        grid.rows[1].marked = vec![false; 10];
+        grid.fill = Some(Date { year: 2024, month: 2, day: 5 });
+        grid.highlight = Some(Date { year: 2024, month: 2, day: 5 });
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "2024-02  | 3456789012");
+        assert_eq!(lines[1], "read     | X·       X");
+        assert_eq!(grid.render_numbers(), "read: 3,12\na|b: -\n");
+        assert!(grid.render_markdown().starts_with("| 2024-02 | 3 | 4 | 5 |"));
+
+        // 2024-02-03 is a saturday, weeks start on the 5th and 12th
+        assert_eq!(grid.weeks(), vec![0..2, 2..9, 9..10]);
+        assert_eq!(grid.highlight_column(), Some(2));
+
+        grid.two_row_header = true;
+        assert_eq!(grid.render_text(false).lines().next().unwrap(), "2024-02  |        111");
+    }
+
+    #[test]
+    fn test_paused_label() {
+        let mut grid = grid();
+        grid.rows[0].paused = true;
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "2024-02      | 12345678901234567890123456789");
+        assert!(lines[1].starts_with("read (paused)| X"));
+    }
+
+    #[test]
+    fn test_weekly_goal() {
+        // march 2024 starts on a friday: weeks 1-3, 4-10, 11-17, 18-24, 25-31
+        let mut marked = vec![false; 31];
+        for day in [1, 4, 5, 6, 11, 12, 18, 19, 20, 21, 25, 26, 27] {
+            marked[day - 1] = true;
+        }
+
+        let grid = MonthGrid {
+            year: 2024,
+            month: 3,
+            rows: vec![
+                GridRow { name: "gym".to_owned(), color: None, marked, paused: false, weekly_goal: Some(3) },
+                GridRow { name: "read".to_owned(), color: None, marked: vec![false; 31], paused: false, weekly_goal: None },
+            ],
+            name_width: None,
+            fill: None,
+            weekly: true,
+            highlight: None,
+            two_row_header: false,
+            palette: Palette::default(),
+            window: None,
+        };
+
+        assert_eq!(grid.weeks(), vec![0..3, 3..10, 10..17, 17..24, 24..31]);
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert!(lines[0].ends_with("1 | 12345"));
+        // the first week needs 2 of 3 days, the last full week 3 of 7
+        assert!(lines[1].ends_with(" | ✗✓✗✓✓"));
+        assert!(lines[2].ends_with(" |      "));
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut grid = grid();
+        grid.fill = Some(Date { year: 2024, month: 2, day: 4 });
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[1], format!("read     | X··{}X", " ".repeat(25)));
+        assert_eq!(lines[2], format!("a|b      | ···{}", " ".repeat(26)));
+
+        grid.fill = Some(Date { year: 2024, month: 3, day: 1 });
+        let text = grid.render_text(false);
+        assert_eq!(text.lines().nth(2).unwrap(), format!("a|b      | {}", "·".repeat(29)));
+
+        grid.fill = Some(Date { year: 2024, month: 1, day: 31 });
+        let text = grid.render_text(false);
+        assert_eq!(text.lines().nth(2).unwrap(), format!("a|b      | {}", " ".repeat(29)));
+    }
+
+    #[test]
+    fn test_highlight_today() {
+        let mut grid = grid();
+        assert_eq!(grid.highlight_column(), None);
+
+        grid.highlight = Some(Date { year: 2024, month: 2, day: 1 });
+        assert_eq!(grid.highlight_column(), Some(0));
+        grid.highlight = Some(Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(grid.highlight_column(), Some(28));
+        grid.highlight = Some(Date { year: 2024, month: 3, day: 1 });
+        assert_eq!(grid.highlight_column(), None);
+
+        grid.highlight = Some(Date { year: 2024, month: 2, day: 2 });
+        assert!(!grid.render_text(false).contains('\x1b'));
+
+        let text = grid.render_text(true);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[1], format!("read     | X{} {}X", color::accent(" "), " ".repeat(25)));
+    }
+
+    #[test]
+    fn test_calendar_week_start() {
+        // march 2024 starts on a friday, september 2024 on a sunday
+        let mut calendar = Calendar { year: 2024, month: 3, marked: vec![false; 31], week_start: Weekday::Mon };
+        assert_eq!(calendar.leading_blanks(), 4);
+        calendar.week_start = Weekday::Sun;
+        assert_eq!(calendar.leading_blanks(), 5);
+
+        calendar.month = 9;
+        assert_eq!(calendar.leading_blanks(), 0);
+        calendar.week_start = Weekday::Mon;
+        assert_eq!(calendar.leading_blanks(), 6);
+    }
+
+    #[test]
+    fn test_calendar_render() {
+        let mut marked = vec![false; 29];
+        marked[1] = true;
+        let calendar = Calendar { year: 2024, month: 2, marked, week_start: Weekday::Sun };
+
+        let text = calendar.render(Locale::En);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "February 2024");
+        assert_eq!(lines[1], " Sun Mon Tue Wed Thu Fri Sat");
+        assert_eq!(lines[2], "                  1   2*  3");
+        assert_eq!(lines[3], "  4   5   6   7   8   9  10");
+        assert_eq!(lines[6], " 25  26  27  28  29");
+        assert_eq!(lines.len(), 7);
+
+        let text = calendar.render(Locale::De);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "Februar 2024");
+        assert_eq!(lines[1], " So  Mo  Di  Mi  Do  Fr  Sa");
+        assert_eq!(lines[2], "                  1   2*  3");
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("read", 4), "read");
+        assert_eq!(truncate("reading", 4), "rea…");
+        assert_eq!(truncate("🏃🏃🏃", 2), "🏃…");
+    }
+
+    /// Splits a markdown table row on unescaped pipes.
+    fn cells(line: &str) -> Vec<String> {
+        let mut cells = vec![];
+        let mut current = String::new();
+        let mut escaped = false;
+        for c in line.trim().trim_start_matches('|').trim_end_matches('|').chars() {
+            if escaped {
+                current.push(c);
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '|' {
+                cells.push(current.trim().to_owned());
+                current.clear();
+            } else {
+                current.push(c);
+            }
+        }
+        cells.push(current.trim().to_owned());
+        cells
+    }
+
+    #[test]
+    fn test_render_text() {
+        let text = grid().render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "2024-02  | 12345678901234567890123456789");
+        assert_eq!(lines[1], format!("read     | X{}X", " ".repeat(27)));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn test_two_row_header() {
+        let mut grid = grid();
+        grid.month = 3;
+        grid.rows.truncate(1);
+        grid.rows[0].marked = vec![false; 31];
+        grid.rows[0].marked[22] = true;
+        grid.two_row_header = true;
+
+        let text = grid.render_text(false);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines[0], "2024-03  |          1111111111222222222233");
+        assert_eq!(lines[1], "         | 1234567890123456789012345678901");
+        assert_eq!(lines[2], format!("read     | {}X{}", " ".repeat(22), " ".repeat(8)));
+        assert_eq!(lines[0].find("2222").unwrap() + 3, lines[2].find('X').unwrap());
+    }
+
+    #[test]
+    fn test_render_text_grouped() {
+        let groups = vec![
+            ("books".to_owned(), vec!["read".to_owned()]),
+            ("(untagged)".to_owned(), vec!["a|b".to_owned()]),
+        ];
+
+        let text = grid().render_text_grouped(&groups, false);
+        let lines = text.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "2024-02  | 12345678901234567890123456789");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "[books]");
+        assert!(lines[3].starts_with("read     | X"));
+        assert_eq!(lines[4], "");
+        assert_eq!(lines[5], "[(untagged)]");
+        assert!(lines[6].starts_with("a|b      | "));
+        assert_eq!(lines.len(), 7);
+    }
+
+    #[test]
+    fn test_render_numbers() {
+        let mut grid = grid();
+        grid.rows[0].marked[4] = true;
+
+        assert_eq!(grid.render_numbers(), "read: 1,5,29\na|b: -\n");
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = grid().render_markdown();
+        let lines = markdown.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 4);
+
+        for line in &lines {
+            assert!(line.starts_with('|') && line.ends_with('|'));
+            assert_eq!(cells(line).len(), 30);
+        }
+
+        assert!(cells(lines[1]).iter().all(|c| c == "---" || c == ":-:"));
+        assert_eq!(cells(lines[2])[0], "read");
+        assert_eq!(cells(lines[2])[1], "✅");
+        assert_eq!(cells(lines[2])[2], "");
+        assert_eq!(cells(lines[3])[0], "a|b");
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{error::CliError, storage::Entry};
+
+/// Serializes entries as an iCalendar document with one all-day event per entry.
+/// `stamp` is the DTSTAMP value in `YYYYMMDDTHHMMSSZ` form.
+pub fn to_ical(entries: &[Entry], stamp: &str) -> Result<String, CliError> {
+
+    let mut result = String::new();
+    push_line(&mut result, "BEGIN:VCALENDAR");
+    push_line(&mut result, "VERSION:2.0");
+    push_line(&mut result, "PRODID:-//htrackr//htrackr//EN");
+    push_line(&mut result, "CALSCALE:GREGORIAN");
+
+    for entry in entries {
+        let start = entry.date.to_naive()?.format("%Y%m%d").to_string();
+        let end = entry.date.add_days(1).to_naive()?.format("%Y%m%d").to_string();
+
+        push_line(&mut result, "BEGIN:VEVENT");
+        push_line(&mut result, &format!("UID:{}-{}@htrackr", entry.habit_id, start));
+        push_line(&mut result, &format!("DTSTAMP:{}", stamp));
+        push_line(&mut result, &format!("DTSTART;VALUE=DATE:{}", start));
+        push_line(&mut result, &format!("DTEND;VALUE=DATE:{}", end));
+        push_line(&mut result, &format!("SUMMARY:{}", escape_text(&entry.name)));
+        if let Some(color) = &entry.color {
+            push_line(&mut result, &format!("COLOR:{}", color));
+        }
+        push_line(&mut result, "TRANSP:TRANSPARENT");
+        push_line(&mut result, "END:VEVENT");
+    }
+
+    push_line(&mut result, "END:VCALENDAR");
+
+    Ok(result)
+}
+
+fn escape_text(text: &str) -> String {
+
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            ';' => result.push_str("\\;"),
+            ',' => result.push_str("\\,"),
+            '\n' => result.push_str("\\n"),
+            '\r' => (),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Appends a content line, folding it at 75 octets as required by RFC 5545.
+fn push_line(result: &mut String, line: &str) {
+
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            result.push_str("\r\n ");
+            width = 1;
+        }
+        result.push(c);
+        width += c.len_utf8();
+    }
+
+    result.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::Date;
+
+    fn entry(id: &str, name: &str, date: Date) -> Entry {
+        Entry { habit_id: id.to_owned(), name: name.to_owned(), color: None, date }
+    }
+
+    #[test]
+    fn test_to_ical() {
+        let entries = vec![
+            entry("hbt_1", "read", Date { year: 2024, month: 2, day: 28 }),
+            entry("hbt_1", "read", Date { year: 2024, month: 2, day: 29 }),
+            entry("hbt_2", "gym, weights", Date { year: 2024, month: 12, day: 31 }),
+        ];
+
+        let ics = to_ical(&entries, "20240101T000000Z").unwrap();
+
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert_eq!(ics.matches("END:VEVENT").count(), 3);
+        assert!(ics.contains("DTSTART;VALUE=DATE:20240229\r\nDTEND;VALUE=DATE:20240301\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20241231\r\nDTEND;VALUE=DATE:20250101\r\n"));
+        assert!(ics.contains("SUMMARY:gym\\, weights\r\n"));
+        assert!(ics.contains("UID:hbt_1-20240228@htrackr"));
+        assert!(ics.contains("UID:hbt_1-20240229@htrackr"));
+    }
+
+    #[test]
+    fn test_push_line_folds_long_lines() {
+        let mut result = String::new();
+        push_line(&mut result, &"a".repeat(100));
+
+        let lines = result.split("\r\n").collect::<Vec<&str>>();
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1], format!(" {}", "a".repeat(25)));
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::Weekday;
+
+use crate::error::CliError;
+
+/// Language of weekday and month names in displays. Stored dates are always ISO.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Locale {
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+pub const LOCALES: [&str; 4] = ["en", "de", "fr", "es"];
+
+const WEEKDAYS: [[&str; 7]; 4] = [
+    ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"],
+    ["lun", "mar", "mer", "jeu", "ven", "sam", "dim"],
+    ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+];
+
+const MONTHS: [[&str; 12]; 4] = [
+    ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"],
+    ["Januar", "Februar", "März", "April", "Mai", "Juni", "Juli", "August", "September", "Oktober", "November", "Dezember"],
+    ["janvier", "février", "mars", "avril", "mai", "juin", "juillet", "août", "septembre", "octobre", "novembre", "décembre"],
+    ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio", "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+];
+
+impl Locale {
+
+    pub fn parse(locale: &str) -> Result<Locale, CliError> {
+        match locale.to_lowercase().as_str() {
+            "en" => Ok(Locale::En),
+            "de" => Ok(Locale::De),
+            "fr" => Ok(Locale::Fr),
+            "es" => Ok(Locale::Es),
+            _ => Err(CliError(format!("unsupported locale {}, expected one of {}", locale, LOCALES.join(", ")))),
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Locale::En => 0,
+            Locale::De => 1,
+            Locale::Fr => 2,
+            Locale::Es => 3,
+        }
+    }
+
+    /// Abbreviated weekday name.
+    pub fn weekday(&self, weekday: Weekday) -> &'static str {
+        WEEKDAYS[self.index()][weekday.num_days_from_monday() as usize]
+    }
+
+    /// Full name of `month`, 1 for January.
+    pub fn month(&self, month: i32) -> &'static str {
+        MONTHS[self.index()][(month - 1).clamp(0, 11) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_names() {
+        assert_eq!(Locale::En.weekday(Weekday::Tue), "Tue");
+        assert_eq!(Locale::parse("de").unwrap().weekday(Weekday::Tue), "Di");
+        assert_eq!(Locale::parse("FR").unwrap().weekday(Weekday::Sun), "dim");
+        assert_eq!(Locale::En.month(1), "January");
+        assert_eq!(Locale::De.month(3), "März");
+        assert_eq!(Locale::Es.month(12), "diciembre");
+        assert!(Locale::parse("xx").is_err());
+    }
+}
@@ -3,12 +3,22 @@ use error::CliError;
 mod error;
 mod storage;
 mod commands;
+mod config;
+mod clock;
+mod color;
 mod date;
+mod export;
+mod grid;
+mod ical;
+mod locale;
+mod pager;
+#[cfg(feature = "image")]
+mod png;
+mod stats;
 
 fn main() -> Result<(), CliError> {
 
-    let storage = storage::connect("habits.db")?;
-    commands::cli(&storage)?;
+    commands::cli()?;
 
     Ok(())
 }
@@ -0,0 +1,115 @@
+use std::{env, io::{stdout, IsTerminal, Write}, ops::Range, path::Path, process::{Command, Stdio}};
+
+use crate::error::CliError;
+
+/// Index range of the 1-based `page` for a list of `len` items.
+pub fn page_range(len: usize, page: usize, page_size: usize) -> Result<Range<usize>, CliError> {
+
+    if page == 0 || page_size == 0 {
+        return Err(CliError::new("invalid page, pages and page sizes start at 1"));
+    }
+
+    let pages = len.div_ceil(page_size).max(1);
+    if page > pages {
+        return Err(CliError(format!("invalid page {}, there are {} pages", page, pages)));
+    }
+
+    let start = (page - 1) * page_size;
+    Ok(start..(start + page_size).min(len))
+}
+
+/// Prints `text`, through `$PAGER` if stdout is a terminal that is too short for it.
+pub fn print(text: &str, use_pager: bool) {
+
+    let fits = match terminal_height() {
+        Some(height) => text.lines().count() < height,
+        None => true,
+    };
+
+    if !use_pager || fits || !stdout().is_terminal() || !run_pager(text) {
+        print!("{}", text);
+    }
+}
+
+/// Returns false if the pager could not be started or failed.
+fn run_pager(text: &str) -> bool {
+
+    let pager = env::var("PAGER").unwrap_or("less -R".to_owned());
+
+    // the shell starts fine even when the pager is missing
+    match pager.split_whitespace().next() {
+        Some(program) if program_exists(program) => (),
+        _ => return false,
+    }
+
+    let mut child = match Command::new("sh").arg("-c").arg(&pager).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // the pager may quit before reading everything
+        let _ = stdin.write_all(text.as_bytes());
+    }
+
+    child.wait().is_ok_and(|status| status.success())
+}
+
+/// Whether `program` is a file, or found in `$PATH` when it has no directory.
+fn program_exists(program: &str) -> bool {
+
+    if program.contains('/') {
+        return Path::new(program).is_file();
+    }
+
+    match env::var_os("PATH") {
+        Some(paths) => env::split_paths(&paths).any(|dir| dir.join(program).is_file()),
+        None => false,
+    }
+}
+
+#[cfg(unix)]
+fn terminal_height() -> Option<usize> {
+
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+
+    match (result, size.ws_row) {
+        (0, rows) if rows > 0 => Some(rows as usize),
+        _ => None,
+    }
+}
+
+#[cfg(not(unix))]
+fn terminal_height() -> Option<usize> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(page_range(45, 1, 20).unwrap(), 0..20);
+        assert_eq!(page_range(45, 2, 20).unwrap(), 20..40);
+        assert_eq!(page_range(45, 3, 20).unwrap(), 40..45);
+        assert!(page_range(45, 4, 20).is_err());
+
+        assert_eq!(page_range(40, 2, 20).unwrap(), 20..40);
+        assert!(page_range(40, 3, 20).is_err());
+
+        assert_eq!(page_range(0, 1, 20).unwrap(), 0..0);
+        assert!(page_range(10, 0, 20).is_err());
+        assert!(page_range(10, 1, 0).is_err());
+    }
+
+    #[test]
+    fn test_program_exists() {
+        assert!(program_exists("sh"));
+        assert!(program_exists("/bin/sh"));
+        assert!(!program_exists("htrackr-no-such-pager"));
+        assert!(!program_exists("/nonexistent/less"));
+    }
+}
@@ -0,0 +1,75 @@
+use std::{collections::BTreeSet, io::Cursor};
+
+use image::{ImageFormat, Rgb, RgbImage};
+
+use crate::{date::{DateRange, Date}, error::CliError};
+
+const CELL: usize = 10;
+const GAP: usize = 2;
+/// Weeks a year can touch, see `Date::week_of_year`.
+const WEEKS: usize = 54;
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const EMPTY: Rgb<u8> = Rgb([235, 237, 240]);
+const MARKED: Rgb<u8> = Rgb([64, 196, 99]);
+
+/// Year heatmap of `marked` as a PNG, one column per week and one row per
+/// weekday starting on Monday.
+pub fn heatmap(year: i32, marked: &BTreeSet<Date>) -> Result<Vec<u8>, CliError> {
+
+    let width = WEEKS * (CELL + GAP) + GAP;
+    let height = 7 * (CELL + GAP) + GAP;
+    let mut image = RgbImage::from_pixel(width as u32, height as u32, BACKGROUND);
+
+    let first = Date { year, month: 1, day: 1 };
+    let last = Date { year, month: 12, day: 31 };
+    first.to_naive()?;
+
+    for day in DateRange::new(&first, &last) {
+        let column = day.week_of_year()? as usize - 1;
+        let row = day.day_of_week()?.num_days_from_monday() as usize;
+        let color = match marked.contains(&day) {
+            true => MARKED,
+            false => EMPTY,
+        };
+
+        let x = GAP + column * (CELL + GAP);
+        let y = GAP + row * (CELL + GAP);
+        for line in y..y + CELL {
+            for pixel in x..x + CELL {
+                image.put_pixel(pixel as u32, line as u32, color);
+            }
+        }
+    }
+
+    let mut png = Cursor::new(vec![]);
+    if let Err(err) = image.write_to(&mut png, ImageFormat::Png) {
+        return Err(CliError(format!("failed to encode png: {}", err)));
+    }
+
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heatmap_png() {
+        let marked = BTreeSet::from([Date { year: 2024, month: 3, day: 1 }]);
+        let png = heatmap(2024, &marked).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!(&png[12..16], b"IHDR");
+
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        assert_eq!(width, 650);
+        assert_eq!(height, 86);
+        assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+
+        let image = image::load_from_memory_with_format(&png, ImageFormat::Png).unwrap().to_rgb8();
+        assert_eq!(image.get_pixel(0, 0), &BACKGROUND);
+        assert_eq!(image.get_pixel(GAP as u32, GAP as u32), &EMPTY);
+    }
+}
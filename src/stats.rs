@@ -0,0 +1,328 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::{date::{self, Date}, error::CliError, storage::{Schedule, Storage}};
+
+/// Completion numbers for one habit over a date range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HabitStats {
+    pub name: String,
+    pub from: Date,
+    pub to: Date,
+    pub marked: u32,
+    pub total: u32,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+impl HabitStats {
+    pub fn compute(storage: &Storage, name: &str, from: &Date, to: &Date, today: &Date) -> Result<HabitStats, CliError> {
+
+        // habits with an interval are only expected every few days
+        let interval = storage.get_interval(name)?;
+        let marked = storage.get_marked_days(name, from, to)?.len() as u32;
+        let total = date::count_days(from, to)?.div_ceil(interval);
+
+        Ok(HabitStats {
+            name: name.to_owned(),
+            from: from.clone(),
+            to: to.clone(),
+            marked,
+            total,
+            current_streak: storage.current_streak(name, today)?,
+            longest_streak: storage.longest_streak(name)?,
+        })
+    }
+
+    /// Share of expected days marked, from 0 to 1.
+    pub fn rate(&self) -> f64 {
+        match self.total {
+            0 => 0.0,
+            _ => (self.marked as f64 / self.total as f64).min(1.0),
+        }
+    }
+
+    /// The `stats --json` fields.
+    pub fn json_fields(&self) -> StatsJson<'_> {
+        StatsJson {
+            name: &self.name,
+            marked: self.marked,
+            total: self.total,
+            rate: self.rate(),
+            current_streak: self.current_streak,
+            longest_streak: self.longest_streak,
+        }
+    }
+}
+
+/// One habit in the `stats --json` output.
+#[derive(Debug, Serialize)]
+pub struct StatsJson<'a> {
+    pub name: &'a str,
+    pub marked: u32,
+    pub total: u32,
+    pub rate: f64,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+}
+
+/// Everything `show` prints about one habit for one month.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Overview {
+    pub year: i32,
+    pub month: i32,
+    /// Marked days of the month.
+    pub marked: BTreeSet<Date>,
+    /// Stats of the month up to today.
+    pub stats: HabitStats,
+    pub all_time_rate: f64,
+    pub last_marked: Option<Date>,
+    pub schedule: Schedule,
+}
+
+impl Overview {
+    pub fn compute(storage: &Storage, name: &str, year: i32, month: i32, today: &Date) -> Result<Overview, CliError> {
+
+        let start = Date::first_of_month(year, month)?;
+        let end = Date::last_of_month(year, month)?;
+        let to = end.clone().min(today.clone());
+
+        Ok(Overview {
+            year,
+            month,
+            marked: storage.marked_set(name, &start, &end)?,
+            stats: HabitStats::compute(storage, name, &start, &to, today)?,
+            all_time_rate: storage.lifetime_rate(name, today)?,
+            last_marked: storage.last_marked(name)?,
+            schedule: storage.get_schedule(name)?,
+        })
+    }
+
+    /// The `show --json` fields.
+    pub fn json_fields(&self) -> OverviewJson<'_> {
+        OverviewJson {
+            name: &self.stats.name,
+            schedule: self.schedule.name(),
+            calendar: CalendarJson {
+                month: format!("{:04}-{:02}", self.year, self.month),
+                marked: &self.marked,
+            },
+            streak: StreakJson {
+                current: self.stats.current_streak,
+                longest: self.stats.longest_streak,
+            },
+            rate: RateJson {
+                month: self.stats.rate(),
+                all_time: self.all_time_rate,
+            },
+            last_marked: self.last_marked.as_ref(),
+        }
+    }
+}
+
+/// One habit in the `show --json` output.
+#[derive(Debug, Serialize)]
+pub struct OverviewJson<'a> {
+    pub name: &'a str,
+    pub schedule: &'a str,
+    pub calendar: CalendarJson<'a>,
+    pub streak: StreakJson,
+    pub rate: RateJson,
+    pub last_marked: Option<&'a Date>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalendarJson<'a> {
+    pub month: String,
+    pub marked: &'a BTreeSet<Date>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StreakJson {
+    pub current: u32,
+    pub longest: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RateJson {
+    pub month: f64,
+    pub all_time: f64,
+}
+
+/// Bin size of `trend`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Group {
+    /// Monday to Sunday weeks.
+    Weekly,
+    Monthly,
+}
+
+impl Group {
+    pub const NAMES: [&'static str; 2] = ["weekly", "monthly"];
+
+    pub fn parse(group: &str) -> Result<Group, CliError> {
+        match group {
+            "weekly" => Ok(Group::Weekly),
+            "monthly" => Ok(Group::Monthly),
+            _ => Err(CliError(format!("unknown group {}, expected one of {}", group, Group::NAMES.join(", ")))),
+        }
+    }
+}
+
+/// Marked days in one week or month of a `trend`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bin {
+    /// First day of the bin inside the range.
+    pub start: Date,
+    /// Days of the bin inside the range, fewer than a full bin at the edges.
+    pub days: u32,
+    pub marked: u32,
+}
+
+/// Splits `from` to `to` into weeks or months and counts the marked days in each.
+pub fn bin_counts(marked: &BTreeSet<Date>, from: &Date, to: &Date, group: Group) -> Result<Vec<Bin>, CliError> {
+
+    let mut result: Vec<Bin> = vec![];
+    let mut previous = None;
+
+    for day in date::days_between(from, to)? {
+        let key = match group {
+            Group::Weekly => (day.year, day.week_of_year()? as i32),
+            Group::Monthly => (day.year, day.month),
+        };
+
+        if previous != Some(key) {
+            result.push(Bin { start: day.clone(), days: 0, marked: 0 });
+            previous = Some(key);
+        }
+
+        if let Some(bin) = result.last_mut() {
+            bin.days += 1;
+            if marked.contains(&day) {
+                bin.marked += 1;
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Totals over the stats of several habits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatsSummary {
+    pub habits: usize,
+    pub marked: u32,
+    /// Mean of the habits' completion rates, from 0 to 1.
+    pub average_rate: f64,
+}
+
+impl StatsSummary {
+    pub fn of(stats: &[HabitStats]) -> StatsSummary {
+
+        let average_rate = match stats.len() {
+            0 => 0.0,
+            len => stats.iter().map(|s| s.rate()).sum::<f64>() / len as f64,
+        };
+
+        StatsSummary {
+            habits: stats.len(),
+            marked: stats.iter().map(|s| s.marked).sum(),
+            average_rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::connect_test;
+
+    #[test]
+    fn test_overview_json() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.set_created_at("read", &Date { year: 2024, month: 2, day: 1 }).unwrap();
+        for day in [8, 9, 10] {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+
+        let overview = Overview::compute(&storage, "read", 2024, 3, &today).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&serde_json::to_string(&overview.json_fields()).unwrap()).unwrap();
+
+        assert_eq!(json.get("name").and_then(|v| v.as_str()), Some("read"));
+        assert_eq!(json.get("schedule").and_then(|v| v.as_str()), Some("daily"));
+        assert_eq!(json.get("calendar").and_then(|c| c.get("month")).and_then(|v| v.as_str()), Some("2024-03"));
+        assert_eq!(json.get("calendar").and_then(|c| c.get("marked")), Some(&serde_json::json!(["2024-03-08", "2024-03-09", "2024-03-10"])));
+        assert_eq!(json.get("streak").and_then(|s| s.get("current")).and_then(|v| v.as_f64()), Some(3.0));
+        assert_eq!(json.get("streak").and_then(|s| s.get("longest")).and_then(|v| v.as_f64()), Some(3.0));
+        assert_eq!(json.get("rate").and_then(|r| r.get("month")).and_then(|v| v.as_f64()), Some(0.3));
+        assert!(json.get("rate").and_then(|r| r.get("all_time")).and_then(|v| v.as_f64()).is_some());
+        assert_eq!(json.get("last_marked").and_then(|v| v.as_str()), Some("2024-03-10"));
+    }
+
+    #[test]
+    fn test_bin_counts_weekly() {
+        // friday the 1st to wednesday the 20th of march 2024
+        let from = Date { year: 2024, month: 3, day: 1 };
+        let to = Date { year: 2024, month: 3, day: 20 };
+        let marked = [1, 3, 4, 10, 11, 12, 20].iter()
+            .map(|day| Date { year: 2024, month: 3, day: *day })
+            .collect::<BTreeSet<Date>>();
+
+        let bins = bin_counts(&marked, &from, &to, Group::Weekly).unwrap();
+        assert_eq!(bins, vec![
+            Bin { start: Date { year: 2024, month: 3, day: 1 }, days: 3, marked: 2 },
+            Bin { start: Date { year: 2024, month: 3, day: 4 }, days: 7, marked: 2 },
+            Bin { start: Date { year: 2024, month: 3, day: 11 }, days: 7, marked: 2 },
+            Bin { start: Date { year: 2024, month: 3, day: 18 }, days: 3, marked: 1 },
+        ]);
+
+        let bins = bin_counts(&marked, &from.sub_days(1), &to, Group::Monthly).unwrap();
+        assert_eq!(bins.len(), 2);
+        assert_eq!(bins[0], Bin { start: Date { year: 2024, month: 2, day: 29 }, days: 1, marked: 0 });
+        assert_eq!(bins[1].marked, 7);
+    }
+
+    #[test]
+    fn test_summary() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+        let from = Date { year: 2024, month: 3, day: 1 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        for day in 1..=10 {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        for day in [2, 4] {
+            storage.mark_habit("gym", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+
+        let stats = ["read", "gym"].iter()
+            .map(|name| HabitStats::compute(&storage, name, &from, &today, &today).unwrap())
+            .collect::<Vec<HabitStats>>();
+
+        assert_eq!(StatsSummary::of(&stats), StatsSummary { habits: 2, marked: 12, average_rate: 0.6 });
+        assert_eq!(StatsSummary::of(&[]), StatsSummary { habits: 0, marked: 0, average_rate: 0.0 });
+    }
+
+    #[test]
+    fn test_json_fields() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        for day in [1, 2, 3, 9, 10] {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+
+        let from = Date { year: 2024, month: 3, day: 1 };
+        let stats = HabitStats::compute(&storage, "read", &from, &today, &today).unwrap();
+        let json = serde_json::to_string(&stats.json_fields()).unwrap();
+
+        assert_eq!(json, r#"{"name":"read","marked":5,"total":10,"rate":0.5,"current_streak":2,"longest_streak":3}"#);
+    }
+}
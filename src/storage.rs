@@ -1,17 +1,172 @@
-use rusqlite::{params, Connection};
+use std::{cell::RefCell, collections::{BTreeSet, HashMap, HashSet}, io::Write};
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use rusqlite::{backup::Backup, params, Connection, ErrorCode, OpenFlags, Params, Row, Statement};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use crate::{date::Date, error::CliError};
+use crate::{color, config::{self, Config, HabitDefaults}, date::{self, days_between, Date, DayFilter}, error::CliError, export::{ExportStream, StreamFormat}};
+
 
+/// Habit names grouped by tag, `None` for untagged habits.
+pub type TagGroups = Vec<(Option<String>, Vec<String>)>;
+
+/// Receives each SQL statement when `--explain` is set.
+pub type SqlLog = Box<dyn Fn(&str)>;
 
 pub struct Storage {
     conn: Connection,
+    explain: Option<SqlLog>,
+    strict_dates: bool,
+    /// Habit ids by name, filled by `resolve_habit` and cleared when names change.
+    ids: RefCell<HashMap<String, String>>,
+    /// Work queued by `after_commit` until the open transaction commits.
+    pending: RefCell<Vec<Box<dyn FnOnce()>>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub habit_id: String,
+    pub name: String,
+    pub color: Option<String>,
+    pub date: Date,
+}
+
+/// A habit with all of its data, as written by `export` and read by `import`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HabitExport {
+    pub id: String,
+    pub name: String,
+    pub color: Option<String>,
+    #[serde(default)]
+    pub entries: Vec<Date>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub habits_created: usize,
+    pub habits_skipped: usize,
+    pub entries_created: usize,
+    pub entries_skipped: usize,
+}
+
+/// Result of `recompute_derived`.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReplaySummary {
+    pub entries: usize,
+    /// One line per problem found, empty for a consistent database.
+    pub anomalies: Vec<String>,
+}
+
+/// What `most_consistent` ranks habits by.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    /// Current streak.
+    Streak,
+    /// Share of expected days marked since the habit was created.
+    Rate,
+    /// Number of marked days.
+    Count,
+}
+
+impl Metric {
+    pub const NAMES: [&'static str; 3] = ["streak", "rate", "count"];
+
+    pub fn parse(metric: &str) -> Result<Metric, CliError> {
+        match metric {
+            "streak" => Ok(Metric::Streak),
+            "rate" => Ok(Metric::Rate),
+            "count" => Ok(Metric::Count),
+            _ => Err(CliError(format!("unknown metric {}, expected one of {}", metric, Metric::NAMES.join(", ")))),
+        }
+    }
+}
+
+/// Days of the week a habit is expected, other days don't break streaks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    Daily,
+    Weekdays,
+    Weekends,
+}
+
+impl Schedule {
+    pub const NAMES: [&'static str; 3] = ["daily", "weekdays", "weekends"];
+
+    pub fn parse(schedule: &str) -> Result<Schedule, CliError> {
+        match schedule {
+            "daily" => Ok(Schedule::Daily),
+            "weekdays" => Ok(Schedule::Weekdays),
+            "weekends" => Ok(Schedule::Weekends),
+            _ => Err(CliError(format!("unknown schedule {}, expected one of {}", schedule, Schedule::NAMES.join(", ")))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Schedule::Daily => "daily",
+            Schedule::Weekdays => "weekdays",
+            Schedule::Weekends => "weekends",
+        }
+    }
+
+    pub fn includes(&self, weekday: Weekday) -> bool {
+        let weekend = weekday == Weekday::Sat || weekday == Weekday::Sun;
+        match self {
+            Schedule::Daily => true,
+            Schedule::Weekdays => !weekend,
+            Schedule::Weekends => weekend,
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct BatchSummary {
+    pub inserted: usize,
+    pub skipped: usize,
+    /// The (name, date) pairs that were newly marked.
+    pub marked: Vec<(String, Date)>,
 }
 
 impl Storage {
 
+    /// Passes every statement to `log` before it runs, with parameters left as placeholders.
+    pub fn set_explain(&mut self, log: SqlLog) {
+        self.explain = Some(log);
+    }
+
+    /// Fail on malformed dates in the database instead of skipping them with a warning.
+    pub fn set_strict_dates(&mut self, strict: bool) {
+        self.strict_dates = strict;
+    }
+
+    fn log_sql(&self, sql: &str) {
+        if let Some(log) = &self.explain {
+            log(&sql.split_whitespace().collect::<Vec<&str>>().join(" "));
+        }
+    }
+
+    fn execute<P: Params>(&self, sql: &str, params: P) -> rusqlite::Result<usize> {
+        self.log_sql(sql);
+        self.conn.execute(sql, params)
+    }
+
+    fn query_row<T, P, F>(&self, sql: &str, params: P, f: F) -> rusqlite::Result<T>
+    where
+        P: Params,
+        F: FnOnce(&Row<'_>) -> rusqlite::Result<T>,
+    {
+        self.log_sql(sql);
+        self.conn.query_row(sql, params, f)
+    }
+
+    fn prepare(&self, sql: &str) -> rusqlite::Result<Statement<'_>> {
+        self.log_sql(sql);
+        self.conn.prepare(sql)
+    }
+
     fn initialize(&self) -> Result<(), CliError> {
-        let _ = self.conn.execute(
+        let _ = self.execute(
             "
 			create table if not exists habits(
 			id varchar(255) primary key,
@@ -20,7 +175,7 @@ impl Storage {
             [])?;
 
 
-        let _ = self.conn.execute(
+        let _ = self.execute(
             "
             create table if not exists habit_entries(
             habit_id varchar(255),
@@ -29,71 +184,438 @@ impl Storage {
             )",
             [])?;
 
+        // databases from before the index may have the same day marked twice
+        let indexed: i32 = self.query_row("select count(1) from sqlite_master where type = 'index' and name = 'habit_entries_habit_date'", [], |row| row.get(0))?;
+        if indexed == 0 {
+            self.execute("
+                delete from habit_entries where rowid not in
+                (select min(rowid) from habit_entries group by habit_id, date)",
+                [])?;
+            self.execute(
+                "
+                create unique index habit_entries_habit_date
+                on habit_entries(habit_id, date)",
+                [])?;
+        }
+
+        let _ = self.execute(
+            "
+            create table if not exists habit_tags(
+            habit_id varchar(255),
+            tag varchar(255),
+            primary key (habit_id, tag),
+            foreign key (habit_id) references habits(id)
+            )",
+            [])?;
+
+        let _ = self.execute(
+            "
+            create table if not exists settings(
+            key varchar(255) primary key,
+            value varchar(255)
+            )",
+            [])?;
+
+        if self.add_column("habits", "position", "integer")? {
+            // keep the previous insertion order for existing habits
+            self.execute("update habits set position = rowid", [])?;
+        }
+        self.add_column("habits", "color", "varchar(255)")?;
+        self.add_column("habits", "created_at", "varchar(10)")?;
+        self.add_column("habit_entries", "note", "text")?;
+        self.add_column("habits", "interval_days", "integer")?;
+        self.add_column("habits", "paused_from", "varchar(10)")?;
+        self.add_column("habits", "paused_to", "varchar(10)")?;
+        self.add_column("habits", "deleted_at", "varchar(10)")?;
+        self.add_column("habits", "weekly_goal", "integer")?;
+        self.add_column("habits", "schedule", "varchar(10)")?;
+
+        Ok(())
+    }
+
+    /// Runs `f` in a transaction, committing if it returns Ok and rolling back otherwise.
+    /// Nested calls join the outer transaction.
+    pub fn with_transaction<F, T>(&self, f: F) -> Result<T, CliError>
+    where
+        F: FnOnce(&Storage) -> Result<T, CliError>,
+    {
+        if !self.conn.is_autocommit() {
+            return f(self);
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let result = match f(self) {
+            Ok(result) => result,
+            Err(err) => {
+                // the rollback may undo habits that were resolved inside it
+                self.forget_ids();
+                self.pending.borrow_mut().clear();
+                return Err(err);
+            },
+        };
+        if let Err(err) = tx.commit() {
+            self.pending.borrow_mut().clear();
+            return Err(err.into());
+        }
+
+        let pending = self.pending.take();
+        for f in pending {
+            f();
+        }
+
+        Ok(result)
+    }
+
+    /// Runs `f` once the open transaction commits, or right away outside of one.
+    /// It never runs if the transaction is rolled back.
+    pub fn after_commit(&self, f: Box<dyn FnOnce()>) {
+
+        match self.conn.is_autocommit() {
+            true => f(),
+            false => self.pending.borrow_mut().push(f),
+        }
+    }
+
+    /// Runs `f` under a savepoint, undoing only its own changes if it fails.
+    /// The surrounding transaction stays open either way.
+    pub fn with_savepoint<F, T>(&self, name: &str, f: F) -> Result<T, CliError>
+    where
+        F: FnOnce(&Storage) -> Result<T, CliError>,
+    {
+        self.execute(&format!("savepoint {}", name), [])?;
+        let queued = self.pending.borrow().len();
+
+        match f(self) {
+            Ok(result) => {
+                self.execute(&format!("release {}", name), [])?;
+                Ok(result)
+            },
+            Err(err) => {
+                self.forget_ids();
+                self.pending.borrow_mut().truncate(queued);
+                self.execute(&format!("rollback to {}", name), [])?;
+                self.execute(&format!("release {}", name), [])?;
+                Err(err)
+            },
+        }
+    }
+
+    /// Runs `f` under a savepoint in a transaction. With `dry_run` its changes are
+    /// rolled back, by failing the savepoint, and only its result is kept.
+    fn with_dry_run<F, T>(&self, name: &str, dry_run: bool, f: F) -> Result<T, CliError>
+    where
+        F: FnOnce(&Storage) -> Result<T, CliError>,
+    {
+        let mut kept = None;
+        self.with_transaction(|s| s.with_savepoint(name, |s| {
+            let result = f(s)?;
+            match dry_run {
+                true => {
+                    kept = Some(result);
+                    Err(CliError::new("dry run"))
+                },
+                false => Ok(result),
+            }
+        }).or_else(|err| kept.take().ok_or(err)))
+    }
+
+    /// Adds a column to an existing table unless it is already there.
+    /// Returns true if the column was added.
+    fn add_column(&self, table: &str, column: &str, definition: &str) -> Result<bool, CliError> {
+
+        let mut stmt = self.prepare(&format!("pragma table_info({})", table))?;
+        let mut columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+
+        if columns.any(|c| c.map(|c| c == column).unwrap_or(false)) {
+            return Ok(false);
+        }
+
+        self.execute(&format!("alter table {} add column {} {}", table, column, definition), [])?;
+
+        Ok(true)
+    }
+
+    /// Copies the database to `path` using the SQLite online backup API,
+    /// which is safe while other connections are writing.
+    pub fn backup_to(&self, path: &str) -> Result<(), CliError> {
+
+        let mut dest = Connection::open(path)?;
+        let backup = Backup::new(&self.conn, &mut dest)?;
+        backup.run_to_completion(100, std::time::Duration::from_millis(10), None)?;
+
+        Ok(())
+    }
+
+    /// Writes a compacted copy of the database to `path`, which must not exist yet.
+    pub fn vacuum_into(&self, path: &str) -> Result<(), CliError> {
+
+        self.execute("vacuum into ?1", params![path])?;
+
+        Ok(())
+    }
+
+    /// SQLite's counter of schema changes in the database file.
+    pub fn schema_version(&self) -> Result<i64, CliError> {
+
+        let result: i64 = self.query_row("pragma schema_version", [], |row| row.get(0))?;
+
+        Ok(result)
+    }
+
+    pub fn load_config(&self) -> Result<Config, CliError> {
+
+        let mut config = Config::default();
+
+        let mut stmt = self.prepare("select key, value from settings")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        for row in rows {
+            let (key, value) = row?;
+            config.set(&key, &value)?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> Result<(), CliError> {
+
+        // validate before storing
+        self.load_config()?.set(key, value)?;
+
+        self.execute("insert into settings (key, value) values (?1, ?2) on conflict(key) do update set value = ?2", params![key, value])?;
+
         Ok(())
     }
 
+    pub fn settings(&self) -> Result<Vec<(String, String)>, CliError> {
+
+        let config = self.load_config()?;
+
+        let mut result = vec![];
+        for key in config::KEYS {
+            result.push((key.to_owned(), config.get(key)?));
+        }
+
+        Ok(result)
+    }
+
+    fn generate_habit_id(&self, name: &str, config: &Config) -> Result<String, CliError> {
+
+        if !config.id_slug {
+            let mut id = config.id_prefix.clone();
+            id.push_str(&Uuid::new_v4().to_string());
+            return Ok(id);
+        }
+
+        let uuid = Uuid::new_v4().simple().to_string();
+
+        // use more of the uuid until the id is free
+        for len in (4..=uuid.len()).step_by(4) {
+            let id = format!("{}{}-{}", config.id_prefix, slug(name), &uuid[..len]);
+
+            let taken: i32 = self.query_row("select count(1) from habits where id = ?1",
+            params![id],
+            |row| row.get(0))?;
+
+            if taken == 0 {
+                return Ok(id);
+            }
+        }
+
+        Err(CliError(format!("failed to generate id for habit {}", name)))
+    }
+
     pub fn create_habit(&self, name: &str) -> Result<(), CliError> {
 
         if self.habit_exists(name)? {
             return Err(CliError::new("habit already exists"));
         }
 
-        if name == "" {
+        if name.is_empty() {
             return Err(CliError::new("invaid name"));
         }
 
-        let mut id = "hbt_".to_owned();
-        id.push_str(&Uuid::new_v4().to_string());
+        let config = self.load_config()?;
+        config.validate_name(name)?;
+        let id = self.generate_habit_id(name, &config)?;
 
-        let _ = self.conn.execute(
+        let _ = self.execute(
             "
             insert into habits
-            (id, name)
-            values (?1, ?2)
+            (id, name, position)
+            values (?1, ?2, (select coalesce(max(position), 0) + 1 from habits))
             ",
             params![id, name])?;
 
         Ok(())
     }
 
+    /// Deletes a habit and all of its data for good. Without an active habit of
+    /// that name the trashed habits called `name` are deleted instead.
     pub fn delete_habit(&self, name: &str) -> Result<(), CliError> {
 
-        if !self.habit_exists(name)? {
-            return Err(CliError(format!("habit {} not found", name)));
+        let ids = match self.habit_exists(name)? {
+            true => vec![self.resolve_habit(name)?],
+            false => self.trashed_ids(Some(name))?,
+        };
+        if ids.is_empty() {
+            // fails with the not found error and a suggestion
+            self.resolve_habit(name)?;
+        }
+
+        self.delete_ids(&ids)
+    }
+
+    /// Deletes every habit in the trash for good and returns how many there were.
+    pub fn purge_trash(&self) -> Result<usize, CliError> {
+
+        let ids = self.trashed_ids(None)?;
+        self.delete_ids(&ids)?;
+
+        Ok(ids.len())
+    }
+
+    fn trashed_ids(&self, name: Option<&str>) -> Result<Vec<String>, CliError> {
+
+        let mut stmt = self.prepare("select id from habits where deleted_at is not null and (?1 is null or name = ?1)")?;
+        let rows = stmt.query_map(params![name], |row| row.get::<_, String>(0))?;
+
+        let mut result = vec![];
+        for row in rows {
+            result.push(row?);
+        }
+
+        Ok(result)
+    }
+
+    fn delete_ids(&self, ids: &[String]) -> Result<(), CliError> {
+
+        self.forget_ids();
+
+        self.with_transaction(|s| {
+            for id in ids {
+                // delete all foreign keys first
+                s.execute("delete from habit_entries where habit_id = ?1", params![id])?;
+                s.execute("delete from habit_tags where habit_id = ?1", params![id])?;
+
+                s.execute("delete from habits where id = ?1", params![id])?;
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Moves a habit to the trash, it keeps its entries until deleted permanently.
+    pub fn soft_delete(&self, name: &str, today: &Date) -> Result<(), CliError> {
+
+        let id = self.resolve_habit(name)?;
+        self.forget_ids();
+        self.execute("update habits set deleted_at = ?1 where id = ?2", params![today.to_string()?, id])?;
+
+        Ok(())
+    }
+
+    /// Restores the most recently trashed habit called `name`.
+    pub fn restore(&self, name: &str) -> Result<(), CliError> {
+
+        if self.habit_exists(name)? {
+            return Err(CliError(format!("habit {} already exists, rename it before restoring", name)));
         }
-        
-        // delete all foreign keys first
-        let id = self.get_habit_id(name)?;
-        self.conn.execute("delete from habit_entries where habit_id = ?1", params![id])?;
 
-        self.conn.execute("delete from habits where name = ?1", params![name])?;
+        let result = self.query_row("
+            select id from habits where name = ?1 and deleted_at is not null
+            order by deleted_at desc, rowid desc limit 1",
+            params![name],
+            |row| row.get::<_, String>(0));
+
+        let id = match result {
+            Ok(id) => id,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Err(CliError(format!("habit {} not found in trash", name))),
+            Err(err) => return Err(err.into()),
+        };
+
+        self.execute("update habits set deleted_at = null where id = ?1", params![id])?;
 
         Ok(())
     }
 
+    /// Trashed habits with the day they were deleted, most recent first.
+    pub fn list_trashed(&self) -> Result<Vec<(String, Date)>, CliError> {
+
+        let mut stmt = self.prepare("select name, deleted_at from habits where deleted_at is not null order by deleted_at desc, rowid desc")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut result = vec![];
+        for row in rows {
+            let (name, deleted_at) = row?;
+            result.push((name, Date::from_string(&deleted_at)?));
+        }
+
+        Ok(result)
+    }
+
+    /// Deletes every habit and entry.
+    pub fn clear_all(&self) -> Result<(), CliError> {
+
+        self.forget_ids();
+        self.with_transaction(|s| {
+            s.execute("delete from habit_entries", [])?;
+            s.execute("delete from habit_tags", [])?;
+            s.execute("delete from habits", [])?;
+            Ok(())
+        })
+    }
+
     pub fn rename_habit(&self, name: &str, new_name: &str) -> Result<(), CliError> {
 
-        if !self.habit_exists(name)? {
-            return Err(CliError(format!("habit {} not found", name)));
+        let id = self.resolve_habit(name)?;
+        self.load_config()?.validate_name(new_name)?;
+
+        if new_name != name && self.habit_exists(new_name)? {
+            return Err(CliError(format!("habit {} already exists", new_name)));
         }
 
-        let _ = self.conn.execute("update habits set name = ?1 where name = ?2", params![new_name, name])?;
+        self.forget_ids();
+        let _ = self.execute("update habits set name = ?1 where id = ?2", params![new_name, id])?;
 
         Ok(())
     }
 
+    /// Like `rename_habit`, but a taken name gets the first free suffix like
+    /// ` (2)` instead of failing. Returns the name that was used.
+    pub fn rename_habit_auto_suffix(&self, name: &str, new_name: &str) -> Result<String, CliError> {
+
+        let mut candidate = new_name.to_owned();
+        let mut n = 2;
+        while candidate != name && self.habit_exists(&candidate)? {
+            candidate = format!("{} ({})", new_name, n);
+            n += 1;
+        }
+
+        self.rename_habit(name, &candidate)?;
+
+        Ok(candidate)
+    }
+
     pub fn habit_exists(&self, name: &str) -> Result<bool, CliError> {
 
-        let result: i32 = self.conn.query_row("select count(1) from habits where name = ?1",
+        let result: i32 = self.query_row("select count(1) from habits where name = ?1 and deleted_at is null",
         params![name],
         |row| row.get(0))?;
 
         Ok(result > 0)
     }
 
+    pub fn habit_count(&self) -> Result<u32, CliError> {
+
+        let result: u32 = self.query_row("select count(1) from habits where deleted_at is null", [], |row| row.get(0))?;
+
+        Ok(result)
+    }
+
     pub fn habit_list(&self) -> Result<Vec<String>, CliError> {
 
-        let mut stmt = self.conn.prepare("select name from habits")?;
+        let mut stmt = self.prepare("select name from habits where deleted_at is null order by position, name collate nocase")?;
 
         let string_iter = stmt.query_map([], |row| {
             let column: String = row.get(0)?;
@@ -110,182 +632,2501 @@ impl Storage {
         Ok(result)
     }
 
-    pub fn get_habit_id(&self, name: &str) -> Result<String, CliError> {
-
-        let result: Result<String, rusqlite::Error> = self.conn.query_row("select id from habits where name = ?1",
-        params![name],
-        |row| row.get(0));
+    /// Moves a habit to `position` (1-based) in the list and renumbers the rest.
+    pub fn set_position(&self, name: &str, position: usize) -> Result<(), CliError> {
 
-        match result {
-            Ok(r) => Ok(r),
-            Err(_) => Err(CliError(format!("habit {} not found", name))),
+        if position < 1 {
+            return Err(CliError::new("position must be at least 1"));
         }
-    }
 
-    pub fn mark_habit(&self, name: &str, date: &Date) -> Result<(), CliError> {
-        let date = date.to_string()?;
+        let id = self.resolve_habit(name)?;
 
-        let id = self.get_habit_id(name)?;
+        let mut ids = {
+            let mut stmt = self.prepare("select id from habits where deleted_at is null order by position, name collate nocase")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<String>, rusqlite::Error>>()?
+        };
 
-        let result: i32 = self.conn.query_row("select count(1) from habit_entries where habit_id = ?1 and date = ?2",
-        params![id, date],
-        |row| row.get(0))?;
+        ids.retain(|i| *i != id);
+        let index = (position - 1).min(ids.len());
+        ids.insert(index, id);
+
+        self.with_transaction(|s| {
+            for (i, id) in ids.iter().enumerate() {
+                s.execute("update habits set position = ?1 where id = ?2", params![i as i64 + 1, id])?;
+            }
+            Ok(())
+        })
+    }
 
-        if result > 0 {
-            return Err(CliError(format!("habit {} already marked for {} date", name, date)));
+    /// Sets the display color of a habit, `None` clears it.
+    pub fn set_color(&self, name: &str, color: Option<&str>) -> Result<(), CliError> {
+
+        if let Some(color) = color {
+            color::validate(color)?;
         }
 
-        self.conn.execute("insert into habit_entries (habit_id, date) values (?1, ?2)", params![id, date])?;
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set color = ?1 where id = ?2", params![color, id])?;
 
         Ok(())
     }
 
-    pub fn unmark_habit(&self, name: &str, date: &Date) -> Result<(), CliError> {
-
-        let date = date.to_string()?;
-        let id = self.get_habit_id(name)?;
-
-        let result: i32 = self.conn.query_row("select count(1) from habit_entries where habit_id = ?1 and date = ?2",
-        params![id, date],
-        |row| row.get(0))?;
+    pub fn add_tag(&self, name: &str, tag: &str) -> Result<(), CliError> {
 
-        if result == 0 {
-            return Err(CliError(format!("habit {} is not marked for {} date", name, date)));
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Err(CliError::new("invalid tag"));
         }
 
-        self.conn.execute("delete from habit_entries where habit_id = ?1 and date = ?2", params![id, date])?;
+        let id = self.resolve_habit(name)?;
+        self.execute("insert into habit_tags (habit_id, tag) values (?1, ?2) on conflict do nothing", params![id, tag])?;
 
         Ok(())
     }
 
-    pub fn get_marked_days(&self, name: &str, date_start: &Date, date_end: &Date) -> Result<Vec<Date>, CliError> {
+    pub fn remove_tag(&self, name: &str, tag: &str) -> Result<(), CliError> {
 
-        let date_start = date_start.to_string()?;
-        let date_end = date_end.to_string()?;
+        let id = self.resolve_habit(name)?;
+        let removed = self.execute("delete from habit_tags where habit_id = ?1 and tag = ?2", params![id, tag])?;
 
-        let id = self.get_habit_id(name)?;
+        if removed == 0 {
+            return Err(CliError(format!("habit {} is not tagged {}", name, tag)));
+        }
 
-        let mut stmt = self.conn.prepare("select date from habit_entries where habit_id = ?1 and date between ?2 and ?3")?;
+        Ok(())
+    }
 
-        let string_iter = stmt.query_map(params![id, date_start, date_end], |row| {
-            let column: String = row.get(0)?;
-            Ok(column)
-        })?;
+    /// Renames `tag` on every habit, merging into `new_tag` where a habit already has it.
+    /// Returns the number of habits affected.
+    pub fn rename_tag(&self, tag: &str, new_tag: &str) -> Result<usize, CliError> {
 
-        let mut result: Vec<Date> = vec![];
-        for string_result in string_iter {
-            let string_value: String = string_result?;
-            let parsed = Date::from_string(&string_value);
-            match parsed {
-                Ok(r) => result.push(r),
-                Err(_) => (),
-            };
+        let new_tag = new_tag.trim();
+        if new_tag.is_empty() {
+            return Err(CliError::new("invalid tag"));
         }
 
-        Ok(result)
+        self.with_transaction(|s| {
+            s.execute("
+                insert into habit_tags (habit_id, tag)
+                select habit_id, ?2 from habit_tags where tag = ?1
+                on conflict do nothing", params![tag, new_tag])?;
+
+            let affected = s.execute("delete from habit_tags where tag = ?1 and tag != ?2", params![tag, new_tag])?;
+            Ok(affected)
+        })
     }
 
-}
+    /// Removes `tag` from every habit, the habits themselves are kept.
+    /// Returns the number of habits affected.
+    pub fn delete_tag(&self, tag: &str) -> Result<usize, CliError> {
 
-fn connect_test() -> Result<Storage, CliError> {
-    let mut path = "./db_test/".to_string();
-    path.push_str(&Uuid::new_v4().to_string());
-    path.push_str(".db");
-    connect(&path)
-}
+        self.with_transaction(|s| {
+            let affected = s.execute("delete from habit_tags where tag = ?1", params![tag])?;
+            Ok(affected)
+        })
+    }
 
-pub fn connect(path: &str) -> Result<Storage, CliError> {
-    let conn = Connection::open(path);
+    pub fn get_tags(&self, name: &str) -> Result<Vec<String>, CliError> {
 
-    let storage = Storage {
-        conn: conn.expect("failed to initialize storage"),
-    };
+        let id = self.resolve_habit(name)?;
 
-    storage.initialize()?;
+        let mut stmt = self.prepare("select tag from habit_tags where habit_id = ?1 order by tag")?;
+        let rows = stmt.query_map(params![id], |row| row.get::<_, String>(0))?;
 
-    Ok(storage)
-}
+        let mut result = vec![];
+        for row in rows {
+            result.push(row?);
+        }
 
-#[cfg(test)]
-mod tests {
-    use clap::builder::Str;
+        Ok(result)
+    }
 
-    use super::*;
-    #[test]
-    fn test_create_habit() {
-        let storage = connect_test().unwrap();
+    /// Habit names per tag, tags sorted by name and habits in list order.
+    /// Untagged habits are listed last under `None`.
+    pub fn habits_by_tag(&self) -> Result<TagGroups, CliError> {
+
+        let mut stmt = self.prepare("
+            select habit_tags.tag, habits.name from habits
+            left join habit_tags on habit_tags.habit_id = habits.id
+            where habits.deleted_at is null
+            order by habit_tags.tag is null, habit_tags.tag, habits.position, habits.name collate nocase")?;
+
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut result: TagGroups = vec![];
+        for row in rows {
+            let (tag, name) = row?;
+            match result.last_mut() {
+                Some((last, names)) if *last == tag => names.push(name),
+                _ => result.push((tag, vec![name])),
+            }
+        }
 
-        storage.create_habit("read").unwrap();
-        let exists = storage.habit_exists("read").unwrap();
-        assert!(exists);
+        Ok(result)
     }
 
-    #[test]
-    fn test_delete_habit() {
-        let storage = connect_test().unwrap();
+    /// Expects the habit every `days` days instead of daily.
+    pub fn set_interval(&self, name: &str, days: u32) -> Result<(), CliError> {
 
-        storage.create_habit("read").unwrap();
-        storage.delete_habit("read").unwrap();
-        let exists = storage.habit_exists("read").unwrap();
-        assert!(!exists);
+        if days == 0 {
+            return Err(CliError::new("invalid interval, expected at least 1 day"));
+        }
+
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set interval_days = ?1 where id = ?2", params![days, id])?;
+
+        Ok(())
     }
 
-    #[test]
-    fn test_rename_habit() {
-        let storage = connect_test().unwrap();
+    /// Days between scheduled completions, 1 for daily habits.
+    pub fn get_interval(&self, name: &str) -> Result<u32, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let result: Option<u32> = self.query_row("select interval_days from habits where id = ?1",
+        params![id],
+        |row| row.get(0))?;
+
+        Ok(result.unwrap_or(1))
+    }
+
+    pub fn set_schedule(&self, name: &str, schedule: Schedule) -> Result<(), CliError> {
+
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set schedule = ?1 where id = ?2", params![schedule.name(), id])?;
+
+        Ok(())
+    }
+
+    /// Gives a new habit the default tag and schedule.
+    pub fn apply_habit_defaults(&self, name: &str, defaults: &HabitDefaults) -> Result<(), CliError> {
+
+        if let Some(tag) = &defaults.tag {
+            self.add_tag(name, tag)?;
+        }
+        if defaults.schedule != Schedule::Daily {
+            self.set_schedule(name, defaults.schedule)?;
+        }
+
+        Ok(())
+    }
+
+    /// Days of the week the habit is expected, daily unless set.
+    pub fn get_schedule(&self, name: &str) -> Result<Schedule, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let result: Option<String> = self.query_row("select schedule from habits where id = ?1",
+        params![id],
+        |row| row.get(0))?;
+
+        match result {
+            Some(schedule) => Schedule::parse(&schedule),
+            None => Ok(Schedule::Daily),
+        }
+    }
+
+    /// Sets how many days a week the habit should be marked, `None` clears it.
+    pub fn set_weekly_goal(&self, name: &str, goal: Option<u32>) -> Result<(), CliError> {
+
+        if let Some(goal) = goal {
+            if !(1..=7).contains(&goal) {
+                return Err(CliError(format!("invalid goal {}, expected 1 to 7 days a week", goal)));
+            }
+        }
+
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set weekly_goal = ?1 where id = ?2", params![goal, id])?;
+
+        Ok(())
+    }
+
+    pub fn get_weekly_goal(&self, name: &str) -> Result<Option<u32>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let result: Option<u32> = self.query_row("select weekly_goal from habits where id = ?1",
+        params![id],
+        |row| row.get(0))?;
+
+        Ok(result)
+    }
+
+    /// Pauses the habit from `today` until it is resumed.
+    pub fn pause(&self, name: &str, today: &Date) -> Result<(), CliError> {
+
+        if self.is_paused(name, today)? {
+            return Err(CliError(format!("habit {} is already paused", name)));
+        }
+
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set paused_from = ?1, paused_to = null where id = ?2", params![today.to_string()?, id])?;
+
+        Ok(())
+    }
+
+    /// Ends the pause, `today` counts as active again.
+    pub fn resume(&self, name: &str, today: &Date) -> Result<(), CliError> {
+
+        let (from, _) = match self.pause_range(name)? {
+            Some(range) if self.is_paused(name, today)? => range,
+            _ => return Err(CliError(format!("habit {} is not paused", name))),
+        };
+
+        let id = self.resolve_habit(name)?;
+        if from == *today {
+            // paused and resumed on the same day, nothing to skip
+            self.execute("update habits set paused_from = null, paused_to = null where id = ?1", params![id])?;
+        } else {
+            self.execute("update habits set paused_to = ?1 where id = ?2", params![today.sub_days(1).to_string()?, id])?;
+        }
+
+        Ok(())
+    }
+
+    /// First and last paused day, the last is `None` while the pause is ongoing.
+    pub fn pause_range(&self, name: &str) -> Result<Option<(Date, Option<Date>)>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let (from, to): (Option<String>, Option<String>) = self.query_row("select paused_from, paused_to from habits where id = ?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        match (from, to) {
+            (Some(from), Some(to)) => Ok(Some((Date::from_string(&from)?, Some(Date::from_string(&to)?)))),
+            (Some(from), None) => Ok(Some((Date::from_string(&from)?, None))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn is_paused(&self, name: &str, today: &Date) -> Result<bool, CliError> {
+
+        let today = today.to_naive()?;
+
+        match self.pause_range(name)? {
+            Some((from, None)) => Ok(from.to_naive()? <= today),
+            Some((from, Some(to))) => Ok(from.to_naive()? <= today && today <= to.to_naive()?),
+            None => Ok(false),
+        }
+    }
+
+    /// Pause as naive dates for streak gaps, an ongoing pause has no end.
+    fn pause_naive(&self, name: &str) -> Result<Option<(NaiveDate, NaiveDate)>, CliError> {
+
+        match self.pause_range(name)? {
+            Some((from, to)) => {
+                let to = match to {
+                    Some(to) => to.to_naive()?,
+                    None => NaiveDate::MAX,
+                };
+                Ok(Some((from.to_naive()?, to)))
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Id of the habit called `name`, or a not found error.
+    pub fn resolve_habit(&self, name: &str) -> Result<String, CliError> {
+
+        if let Some(id) = self.ids.borrow().get(name) {
+            return Ok(id.clone());
+        }
+
+        let result: Result<String, rusqlite::Error> = self.query_row("select id from habits where name = ?1 and deleted_at is null",
+        params![name],
+        |row| row.get(0));
+
+        match result {
+            Ok(r) => {
+                self.ids.borrow_mut().insert(name.to_owned(), r.clone());
+                Ok(r)
+            },
+            Err(rusqlite::Error::QueryReturnedNoRows) => Err(self.not_found(name)?),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Clears the id cache of `resolve_habit`, needed whenever a name stops
+    /// belonging to the id it was resolved to.
+    fn forget_ids(&self) {
+        self.ids.borrow_mut().clear();
+    }
+
+    /// Not found error for `name`, suggesting the closest habit name if one is close enough.
+    fn not_found(&self, name: &str) -> Result<CliError, CliError> {
+
+        let closest = self.habit_list()?
+            .into_iter()
+            .map(|habit| (edit_distance(name, &habit), habit))
+            .filter(|(distance, habit)| *distance <= 2 && *distance < habit.chars().count())
+            .min();
+
+        match closest {
+            Some((_, habit)) => Ok(CliError(format!("habit {} not found, did you mean '{}'?", name, habit))),
+            None => Ok(CliError(format!("habit {} not found", name))),
+        }
+    }
+
+    /// Marks the habit for `date`, an error if it is already marked.
+    /// Always returns true, see `mark_habit_idempotent`.
+    pub fn mark_habit(&self, name: &str, date: &Date) -> Result<bool, CliError> {
+
+        match self.mark_habit_idempotent(name, date)? {
+            true => Ok(true),
+            false => Err(CliError(format!("habit {} already marked for {} date", name, date.to_string()?))),
+        }
+    }
+
+    /// Marks the habit for `date`. Returns whether the entry is new, false if
+    /// the day was already marked.
+    pub fn mark_habit_idempotent(&self, name: &str, date: &Date) -> Result<bool, CliError> {
+
+        let date = date.to_string()?;
+        let id = self.resolve_habit(name)?;
+
+        let changed = self.execute("insert into habit_entries (habit_id, date) values (?1, ?2) on conflict do nothing", params![id, date])?;
+
+        Ok(changed > 0)
+    }
+
+    /// Marks every (name, date) pair in a single transaction. Entries that are
+    /// already marked are skipped instead of failing the whole batch.
+    pub fn batch_mark(&self, entries: &[(String, Date)]) -> Result<BatchSummary, CliError> {
+
+        let mut ids: HashMap<&str, String> = HashMap::new();
+        for (name, _) in entries {
+            if !ids.contains_key(name.as_str()) {
+                ids.insert(name, self.resolve_habit(name)?);
+            }
+        }
+
+        self.with_transaction(|s| {
+            let mut summary = BatchSummary::default();
+            let mut stmt = s.prepare("insert into habit_entries (habit_id, date) values (?1, ?2) on conflict do nothing")?;
+
+            for (name, date) in entries {
+                let changed = stmt.execute(params![ids[name.as_str()], date.to_string()?])?;
+
+                if changed > 0 {
+                    summary.inserted += 1;
+                    summary.marked.push((name.clone(), date.clone()));
+                } else {
+                    summary.skipped += 1;
+                }
+            }
+
+            Ok(summary)
+        })
+    }
+
+    pub fn unmark_habit(&self, name: &str, date: &Date) -> Result<(), CliError> {
+
+        let date = date.to_string()?;
+        let id = self.resolve_habit(name)?;
+
+        let result: i32 = self.query_row("select count(1) from habit_entries where habit_id = ?1 and date = ?2",
+        params![id, date],
+        |row| row.get(0))?;
+
+        if result == 0 {
+            return Err(CliError(format!("habit {} is not marked for {} date", name, date)));
+        }
+
+        self.execute("delete from habit_entries where habit_id = ?1 and date = ?2", params![id, date])?;
+
+        Ok(())
+    }
+
+    /// Unmarks all days between `start` and `end` (inclusive) accepted by `filter`.
+    /// Returns the number of entries removed.
+    pub fn unmark_range(&self, name: &str, start: &Date, end: &Date, filter: DayFilter) -> Result<usize, CliError> {
+        self.unmark_range_paged(name, start, end, filter, 1000)
+    }
+
+    fn unmark_range_paged(&self, name: &str, start: &Date, end: &Date, filter: DayFilter, page_size: usize) -> Result<usize, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        self.with_transaction(|s| {
+            let mut removed = 0;
+            let mut offset = 0;
+
+            // walk the range in pages so huge ranges don't load every entry at once
+            loop {
+                // page on the stored rows, malformed ones are kept and still take up their place
+                let rows = s.marked_rows_paged(&id, start, end, offset, page_size)?;
+                let mut kept = rows.len();
+
+                for row in &rows {
+                    let day = match s.parse_entry_date(name, row)? {
+                        Some(day) => day,
+                        None => continue,
+                    };
+                    if filter.matches(&day)? {
+                        removed += s.execute("delete from habit_entries where habit_id = ?1 and date = ?2", params![id, row])?;
+                        kept -= 1;
+                    }
+                }
+
+                if rows.len() < page_size {
+                    break;
+                }
+                offset += kept;
+            }
+
+            Ok(removed)
+        })
+    }
+
+    pub fn get_marked_days(&self, name: &str, date_start: &Date, date_end: &Date) -> Result<Vec<Date>, CliError> {
+
+        let date_start = date_start.to_string()?;
+        let date_end = date_end.to_string()?;
+
+        let id = self.resolve_habit(name)?;
+
+        let mut stmt = self.prepare("select date from habit_entries where habit_id = ?1 and date between ?2 and ?3")?;
+
+        let string_iter = stmt.query_map(params![id, date_start, date_end], |row| {
+            let column: String = row.get(0)?;
+            Ok(column)
+        })?;
+
+        let mut result: Vec<Date> = vec![];
+        for string_result in string_iter {
+            let string_value: String = string_result?;
+            if let Some(date) = self.parse_entry_date(name, &string_value)? {
+                result.push(date);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Like `get_marked_days`, as a sorted set for lookups and in order iteration.
+    pub fn marked_set(&self, name: &str, date_start: &Date, date_end: &Date) -> Result<BTreeSet<Date>, CliError> {
+        Ok(self.get_marked_days(name, date_start, date_end)?.into_iter().collect())
+    }
+
+    /// Colors of every habit that has one, by habit name, in one query.
+    pub fn colors(&self) -> Result<HashMap<String, String>, CliError> {
+
+        let mut stmt = self.prepare("select name, color from habits where deleted_at is null and color is not null")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        Ok(rows.collect::<Result<HashMap<String, String>, _>>()?)
+    }
+
+    /// Names of the habits paused on `today`, in one query.
+    pub fn paused_habits(&self, today: &Date) -> Result<HashSet<String>, CliError> {
+
+        let mut stmt = self.prepare("
+            select name from habits
+            where deleted_at is null and paused_from <= ?1 and (paused_to is null or ?1 <= paused_to)")?;
+        let rows = stmt.query_map(params![today.to_string()?], |row| row.get::<_, String>(0))?;
+
+        Ok(rows.collect::<Result<HashSet<String>, _>>()?)
+    }
+
+    /// Weekly goals of every habit that has one, by habit name, in one query.
+    pub fn weekly_goals(&self) -> Result<HashMap<String, u32>, CliError> {
+
+        let mut stmt = self.prepare("select name, weekly_goal from habits where deleted_at is null and weekly_goal is not null")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)))?;
+
+        Ok(rows.collect::<Result<HashMap<String, u32>, _>>()?)
+    }
+
+    /// Marked days of every habit between two dates, by habit name, in one query.
+    /// Habits without marks in the range are left out.
+    pub fn marked_days_in_range(&self, date_start: &Date, date_end: &Date) -> Result<HashMap<String, Vec<Date>>, CliError> {
+
+        let mut stmt = self.prepare("
+            select habits.name, habit_entries.date from habit_entries
+            join habits on habits.id = habit_entries.habit_id
+            where habits.deleted_at is null and habit_entries.date between ?1 and ?2
+            order by habit_entries.date")?;
+
+        let rows = stmt.query_map(params![date_start.to_string()?, date_end.to_string()?], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut result: HashMap<String, Vec<Date>> = HashMap::new();
+        for row in rows {
+            let (name, date) = row?;
+            if let Some(date) = self.parse_entry_date(&name, &date)? {
+                result.entry(name).or_default().push(date);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Parses a stored entry date. Malformed dates are an error with strict
+    /// dates, otherwise they are skipped with a warning.
+    fn parse_entry_date(&self, name: &str, date: &str) -> Result<Option<Date>, CliError> {
+
+        match Date::from_string(date) {
+            Ok(date) => Ok(Some(date)),
+            Err(err) if self.strict_dates => Err(CliError(format!("malformed entry of habit {}: {}", name, err.0))),
+            Err(err) => {
+                eprintln!("warning: skipping malformed entry of habit {}: {}", name, err.0);
+                Ok(None)
+            },
+        }
+    }
+
+    /// Entries whose date is not a valid `YYYY-MM-DD` date, as (habit name, date).
+    /// Other commands skip these, so they are otherwise invisible.
+    pub fn find_invalid_dates(&self) -> Result<Vec<(String, String)>, CliError> {
+
+        Ok(self.invalid_entries()?
+            .into_iter()
+            .map(|(_, name, date)| (name, date))
+            .collect())
+    }
+
+    /// Deletes the entries `find_invalid_dates` reports and returns how many were removed.
+    pub fn remove_invalid_dates(&self) -> Result<usize, CliError> {
+
+        let entries = self.invalid_entries()?;
+
+        self.with_transaction(|s| {
+            for (rowid, _, _) in &entries {
+                s.execute("delete from habit_entries where rowid = ?1", params![rowid])?;
+            }
+            Ok(entries.len())
+        })
+    }
+
+    fn invalid_entries(&self) -> Result<Vec<(i64, String, String)>, CliError> {
+
+        let mut stmt = self.prepare("select habit_entries.rowid, habits.name, habit_entries.date
+            from habit_entries join habits on habits.id = habit_entries.habit_id
+            order by habits.name, habit_entries.date")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)))?;
+
+        let mut result = vec![];
+        for row in rows {
+            let (rowid, name, date) = row?;
+            let canonical = Date::from_string(&date).and_then(|d| d.to_string());
+            if canonical.ok().as_deref() != Some(date.as_str()) {
+                result.push((rowid, name, date));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Rebuilds state derived from `habit_entries`. Nothing is cached yet, so this
+    /// only replays the entries and reports the ones that can't be trusted.
+    pub fn recompute_derived(&self) -> Result<ReplaySummary, CliError> {
+
+        let mut summary = ReplaySummary {
+            entries: self.query_row("select count(1) from habit_entries", [], |row| row.get(0))?,
+            anomalies: vec![],
+        };
+
+        for (_, name, date) in self.invalid_entries()? {
+            summary.anomalies.push(format!("{}: invalid date {}", name, date));
+        }
+
+        let mut stmt = self.prepare("
+            select habit_id, count(1) from habit_entries
+            where habit_id not in (select id from habits)
+            group by habit_id order by habit_id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?)))?;
+        for row in rows {
+            let (id, count) = row?;
+            summary.anomalies.push(format!("{} entries of missing habit {}", count, id));
+        }
+
+        let mut stmt = self.prepare("
+            select distinct habit_id from habit_tags
+            where habit_id not in (select id from habits)
+            order by habit_id")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        for row in rows {
+            summary.anomalies.push(format!("tags of missing habit {}", row?));
+        }
+
+        Ok(summary)
+    }
+
+    /// Like `get_marked_days`, but ordered by date and limited to `limit` stored
+    /// entries after skipping `offset`. Skipped malformed dates can make a page
+    /// shorter than `limit` before the last one.
+    pub fn get_marked_days_paged(&self, name: &str, date_start: &Date, date_end: &Date, offset: usize, limit: usize) -> Result<Vec<Date>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let mut result = vec![];
+        for date in self.marked_rows_paged(&id, date_start, date_end, offset, limit)? {
+            if let Some(date) = self.parse_entry_date(name, &date)? {
+                result.push(date);
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Stored date strings of one page of entries, ordered by date. Pages count
+    /// stored rows, so malformed dates don't shift the pages after them.
+    fn marked_rows_paged(&self, id: &str, date_start: &Date, date_end: &Date, offset: usize, limit: usize) -> Result<Vec<String>, CliError> {
+
+        let mut stmt = self.prepare("select date from habit_entries where habit_id = ?1 and date between ?2 and ?3 order by date limit ?4 offset ?5")?;
+        let rows = stmt.query_map(params![id, date_start.to_string()?, date_end.to_string()?, limit as i64, offset as i64], |row| row.get::<_, String>(0))?;
+
+        let mut result = vec![];
+        for row in rows {
+            result.push(row?);
+        }
+
+        Ok(result)
+    }
+
+    /// All marked entries, optionally limited to one habit and to days on or after `since`,
+    /// ordered by habit name and date.
+    pub fn all_entries(&self, name: Option<&str>, since: Option<&Date>) -> Result<Vec<Entry>, CliError> {
+
+        let id = match name {
+            Some(name) => Some(self.resolve_habit(name)?),
+            None => None,
+        };
+        let since = match since {
+            Some(date) => Some(date.to_string()?),
+            None => None,
+        };
+
+        let mut stmt = self.prepare("
+            select habits.id, habits.name, habits.color, habit_entries.date from habit_entries
+            join habits on habits.id = habit_entries.habit_id
+            where habits.deleted_at is null and (?1 is null or habits.id = ?1)
+            and (?2 is null or habit_entries.date >= ?2)
+            order by habits.name, habit_entries.date")?;
+
+        let rows = stmt.query_map(params![id, since], |row| {
+            let id: String = row.get(0)?;
+            let name: String = row.get(1)?;
+            let color: Option<String> = row.get(2)?;
+            let date: String = row.get(3)?;
+            Ok((id, name, color, date))
+        })?;
+
+        let mut result = vec![];
+        for row in rows {
+            let (habit_id, name, color, date) = row?;
+            if let Some(date) = self.parse_entry_date(&name, &date)? {
+                result.push(Entry { habit_id, name, color, date });
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Number of marked days between `from` and `to` (inclusive), unbounded sides count everything.
+    pub fn count_entries(&self, name: &str, from: Option<&Date>, to: Option<&Date>) -> Result<u32, CliError> {
+
+        let from = match from {
+            Some(date) => Some(date.to_string()?),
+            None => None,
+        };
+        let to = match to {
+            Some(date) => Some(date.to_string()?),
+            None => None,
+        };
+
+        let id = self.resolve_habit(name)?;
+
+        let result: u32 = self.query_row("
+            select count(1) from habit_entries
+            where habit_id = ?1
+            and (?2 is null or date >= ?2)
+            and (?3 is null or date <= ?3)",
+        params![id, from, to],
+        |row| row.get(0))?;
+
+        Ok(result)
+    }
+
+    /// Number of consecutive scheduled completions ending at `today`. Daily
+    /// habits count marked days, if `today` is not marked yet the streak is
+    /// counted from the day before, so it only breaks once a full day has been
+    /// missed. Habits with an interval allow that many days between marks,
+    /// days off the habit's schedule are not counted.
+    pub fn current_streak(&self, name: &str, today: &Date) -> Result<u32, CliError> {
+
+        let id = self.resolve_habit(name)?;
+        let interval = self.get_interval(name)? as i64;
+        let pause = self.pause_naive(name)?;
+        let schedule = self.get_schedule(name)?;
+
+        let mut stmt = self.prepare("select date from habit_entries where habit_id = ?1 and date <= ?2 order by date desc")?;
+        let rows = stmt.query_map(params![id, today.to_string()?], |row| row.get::<_, String>(0))?;
+
+        let mut previous = today.to_naive()?;
+        let mut streak = 0;
+
+        for row in rows {
+            let date = match self.parse_entry_date(name, &row?)? {
+                Some(date) => date.to_naive()?,
+                None => continue,
+            };
+
+            if gap_days(date, previous, pause, schedule) > interval {
+                break;
+            }
+
+            streak += 1;
+            previous = date;
+        }
+
+        Ok(streak)
+    }
+
+    /// Note of the entry on `date`, `None` if there is no note or the day is not marked.
+    pub fn get_note(&self, name: &str, date: &Date) -> Result<Option<String>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let result = self.query_row("select note from habit_entries where habit_id = ?1 and date = ?2",
+        params![id, date.to_string()?],
+        |row| row.get::<_, Option<String>>(0));
+
+        match result {
+            Ok(note) => Ok(note),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Sets the note of the entry on `date`. Errors if the day is not marked,
+    /// notes are never created without an entry.
+    pub fn set_note(&self, name: &str, date: &Date, note: &str) -> Result<(), CliError> {
+
+        let id = self.resolve_habit(name)?;
+        let date = date.to_string()?;
+
+        let updated = self.execute("update habit_entries set note = ?1 where habit_id = ?2 and date = ?3", params![note, id, date])?;
+
+        if updated == 0 {
+            return Err(CliError(format!("habit {} is not marked for {}, mark it before adding a note", name, date)));
+        }
+
+        Ok(())
+    }
+
+    /// Lifetime completion rate from creation (or the first mark) to `today`, from 0 to 1.
+    pub fn lifetime_rate(&self, name: &str, today: &Date) -> Result<f64, CliError> {
+
+        let start = match self.created_at(name)? {
+            Some(date) => date,
+            None => match self.first_marked(name)? {
+                Some(date) => date,
+                None => return Ok(0.0),
+            },
+        };
+
+        let days = date::count_days(&start, today)?;
+        let expected = days.div_ceil(self.get_interval(name)?);
+        if expected == 0 {
+            return Ok(0.0);
+        }
+
+        let marked = self.count_entries(name, Some(&start), Some(today))?;
+        Ok((marked as f64 / expected as f64).min(1.0))
+    }
+
+    /// The best `limit` habits by `metric`, ties sorted by name.
+    pub fn most_consistent(&self, metric: Metric, limit: usize, today: &Date) -> Result<Vec<(String, f64)>, CliError> {
+
+        let mut result = vec![];
+        for name in self.habit_list()? {
+            let value = match metric {
+                Metric::Streak => self.current_streak(&name, today)? as f64,
+                Metric::Rate => self.lifetime_rate(&name, today)?,
+                Metric::Count => self.count_entries(&name, None, None)? as f64,
+            };
+            result.push((name, value));
+        }
+
+        result.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        result.truncate(limit);
+
+        Ok(result)
+    }
+
+    /// Number of distinct habits marked on `date`.
+    pub fn completed_on(&self, date: &Date) -> Result<u32, CliError> {
+
+        let result: u32 = self.query_row("
+            select count(distinct habit_id) from habit_entries
+            join habits on habits.id = habit_entries.habit_id
+            where habits.deleted_at is null and date = ?1",
+        params![date.to_string()?],
+        |row| row.get(0))?;
+
+        Ok(result)
+    }
+
+    /// Number of habits marked on each day from `start` to `end`, including days without any.
+    pub fn daily_totals(&self, start: &Date, end: &Date) -> Result<Vec<(Date, u32)>, CliError> {
+
+        let mut stmt = self.prepare("
+            select date, count(distinct habit_id) from habit_entries
+            join habits on habits.id = habit_entries.habit_id
+            where habits.deleted_at is null and date between ?1 and ?2
+            group by date")?;
+        let rows = stmt.query_map(params![start.to_string()?, end.to_string()?], |row| Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?)))?;
+
+        let mut counts = HashMap::new();
+        for row in rows {
+            let (date, count) = row?;
+            counts.insert(date, count);
+        }
+
+        let mut result = vec![];
+        for day in days_between(start, end)? {
+            let count = counts.get(&day.to_string()?).copied().unwrap_or(0);
+            result.push((day, count));
+        }
+
+        Ok(result)
+    }
+
+    /// Longest streak over all entries, counted like `current_streak`.
+    pub fn longest_streak(&self, name: &str) -> Result<u32, CliError> {
+
+        let id = self.resolve_habit(name)?;
+        let interval = self.get_interval(name)? as i64;
+        let pause = self.pause_naive(name)?;
+        let schedule = self.get_schedule(name)?;
+
+        let mut stmt = self.prepare("select date from habit_entries where habit_id = ?1 order by date")?;
+        let rows = stmt.query_map(params![id], |row| row.get::<_, String>(0))?;
+
+        let mut previous: Option<NaiveDate> = None;
+        let mut streak = 0;
+        let mut longest = 0;
+
+        for row in rows {
+            let date = match self.parse_entry_date(name, &row?)? {
+                Some(date) => date.to_naive()?,
+                None => continue,
+            };
+
+            streak = match previous {
+                Some(p) if gap_days(p, date, pause, schedule) <= interval => streak + 1,
+                _ => 1,
+            };
+            longest = longest.max(streak);
+            previous = Some(date);
+        }
+
+        Ok(longest)
+    }
+
+    /// Every habit in list order, with its entries unless `with_entries` is false.
+    /// `since` drops entries before that day, the habits are always included.
+    pub fn export_all(&self, with_entries: bool, since: Option<&Date>) -> Result<Vec<HabitExport>, CliError> {
+
+        let mut result = vec![];
+
+        let mut stmt = self.prepare("select id, name, color from habits where deleted_at is null order by position, name collate nocase")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+
+        for row in rows {
+            let (id, name, color) = row?;
+            let entries = match with_entries {
+                true => self.all_entries(Some(&name), since)?
+                    .into_iter()
+                    .map(|entry| entry.date)
+                    .collect(),
+                false => vec![],
+            };
+
+            result.push(HabitExport { id, name, color, entries });
+        }
+
+        Ok(result)
+    }
+
+    /// Writes what `export_all` and `all_entries` would return to `w` while the
+    /// rows are read, so large databases are never held in memory.
+    pub fn stream_export<W: Write>(&self, w: &mut W, format: StreamFormat, name: Option<&str>, since: Option<&Date>) -> Result<(), CliError> {
+
+        let id = match name {
+            Some(name) => Some(self.resolve_habit(name)?),
+            None => None,
+        };
+        let since = match since {
+            Some(date) => Some(date.to_string()?),
+            None => None,
+        };
+
+        // same order as the buffered exports
+        let order = match format {
+            StreamFormat::Json => "habits.position, habits.name collate nocase, habit_entries.date",
+            StreamFormat::Csv | StreamFormat::Tsv => "habits.name, habit_entries.date",
+        };
+        let mut stmt = self.prepare(&format!("
+            select habits.id, habits.name, habits.color, habit_entries.date from habits
+            left join habit_entries on habit_entries.habit_id = habits.id
+            and (?2 is null or habit_entries.date >= ?2)
+            where habits.deleted_at is null and (?1 is null or habits.id = ?1)
+            order by {}", order))?;
+
+        let mut stream = ExportStream::begin(w, format)?;
+        let mut rows = stmt.query(params![id, since])?;
+        while let Some(row) = rows.next()? {
+            let habit_id: String = row.get(0)?;
+            let name: String = row.get(1)?;
+            let color: Option<String> = row.get(2)?;
+            let date = match row.get::<_, Option<String>>(3)? {
+                // a skipped date still writes the habit, like the buffered export
+                Some(date) => self.parse_entry_date(&name, &date)?,
+                None => None,
+            };
+            stream.row(&habit_id, &name, color.as_deref(), date.as_ref())?;
+        }
+
+        stream.finish()
+    }
+
+    /// Creates habits that don't exist yet (matched by name) and marks their entries.
+    /// With `dry_run` everything is rolled back, only the summary is returned.
+    pub fn import(&self, habits: &[HabitExport], dry_run: bool) -> Result<ImportSummary, CliError> {
+        self.with_dry_run("import", dry_run, |s| s.import_habits(habits))
+    }
+
+    fn import_habits(&self, habits: &[HabitExport]) -> Result<ImportSummary, CliError> {
+
+        let mut summary = ImportSummary::default();
+
+        for habit in habits {
+            let id = if self.habit_exists(&habit.name)? {
+                summary.habits_skipped += 1;
+                self.resolve_habit(&habit.name)?
+            } else {
+                if let Some(color) = &habit.color {
+                    color::validate(color)?;
+                }
+
+                let taken: i32 = self.query_row("select count(1) from habits where id = ?1",
+                params![habit.id],
+                |row| row.get(0))?;
+
+                let id = match taken {
+                    0 => habit.id.clone(),
+                    _ => self.generate_habit_id(&habit.name, &self.load_config()?)?,
+                };
+
+                self.execute("
+                    insert into habits
+                    (id, name, color, position)
+                    values (?1, ?2, ?3, (select coalesce(max(position), 0) + 1 from habits))",
+                    params![id, habit.name, habit.color])?;
+
+                summary.habits_created += 1;
+                id
+            };
+
+            let mut stmt = self.prepare("insert into habit_entries (habit_id, date) values (?1, ?2) on conflict do nothing")?;
+            for date in &habit.entries {
+                match stmt.execute(params![id, date.to_string()?])? {
+                    0 => summary.entries_skipped += 1,
+                    _ => summary.entries_created += 1,
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Marks (habit name, date) entries, creating habits that don't exist yet.
+    /// With `dry_run` everything is rolled back, only the summary is returned.
+    pub fn import_entries(&self, entries: &[(String, Date)], dry_run: bool) -> Result<ImportSummary, CliError> {
+        self.with_dry_run("import_entries", dry_run, |s| s.import_rows(entries))
+    }
+
+    fn import_rows(&self, entries: &[(String, Date)]) -> Result<ImportSummary, CliError> {
+
+        let mut summary = ImportSummary::default();
+
+        let mut seen = vec![];
+        for (name, _) in entries {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+
+            if self.habit_exists(name)? {
+                summary.habits_skipped += 1;
+            } else {
+                self.create_habit(name)?;
+                summary.habits_created += 1;
+            }
+        }
+
+        let marked = self.batch_mark(entries)?;
+        summary.entries_created = marked.inserted;
+        summary.entries_skipped = marked.skipped;
+
+        Ok(summary)
+    }
+
+    pub fn set_created_at(&self, name: &str, date: &Date) -> Result<(), CliError> {
+
+        let id = self.resolve_habit(name)?;
+        self.execute("update habits set created_at = ?1 where id = ?2", params![date.to_string()?, id])?;
+
+        Ok(())
+    }
+
+    /// Creation date, `None` for habits created before it was recorded.
+    pub fn created_at(&self, name: &str) -> Result<Option<Date>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let result: Option<String> = self.query_row("select created_at from habits where id = ?1",
+        params![id],
+        |row| row.get(0))?;
+
+        match result {
+            Some(date) => self.parse_entry_date(name, &date),
+            None => Ok(None),
+        }
+    }
+
+    pub fn first_marked(&self, name: &str) -> Result<Option<Date>, CliError> {
+        self.edge_marked(name, "asc")
+    }
+
+    pub fn last_marked(&self, name: &str) -> Result<Option<Date>, CliError> {
+        self.edge_marked(name, "desc")
+    }
+
+    /// First valid date in `order`, malformed dates are handled like everywhere else.
+    fn edge_marked(&self, name: &str, order: &str) -> Result<Option<Date>, CliError> {
+
+        let id = self.resolve_habit(name)?;
+
+        let mut stmt = self.prepare(&format!("select date from habit_entries where habit_id = ?1 order by date {}", order))?;
+        let rows = stmt.query_map(params![id], |row| row.get::<_, String>(0))?;
+
+        for row in rows {
+            if let Some(date) = self.parse_entry_date(name, &row?)? {
+                return Ok(Some(date));
+            }
+        }
+
+        Ok(None)
+    }
+
+}
+
+/// Lowercase ascii letters and digits of `name`, everything else collapsed into dashes.
+fn slug(name: &str) -> String {
+
+    let mut result = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            result.push(c.to_ascii_lowercase());
+        } else if !result.is_empty() && !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+
+    let result = result.trim_end_matches('-');
+    if result.is_empty() {
+        return "habit".to_owned();
+    }
+
+    result.to_owned()
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+
+    let b = b.chars().collect::<Vec<char>>();
+
+    // previous row of the distance matrix
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Days from `start` to `end`, not counting paused or unscheduled days in between.
+fn gap_days(start: NaiveDate, end: NaiveDate, pause: Option<(NaiveDate, NaiveDate)>, schedule: Schedule) -> i64 {
+
+    let gap = (end - start).num_days();
+    if gap < 2 {
+        return gap;
+    }
+
+    let skipped = start.iter_days()
+        .skip(1)
+        .take(gap as usize - 1)
+        .filter(|day| {
+            let paused = pause.is_some_and(|(from, to)| from <= *day && *day <= to);
+            paused || !schedule.includes(day.weekday())
+        })
+        .count() as i64;
+
+    gap - skipped
+}
+
+#[cfg(test)]
+pub fn connect_test() -> Result<Storage, CliError> {
+    std::fs::create_dir_all("./db_test/").expect("failed to create test directory");
+    let mut path = "./db_test/".to_string();
+    path.push_str(&Uuid::new_v4().to_string());
+    path.push_str(".db");
+    connect(&path)
+}
+
+pub fn connect(path: &str) -> Result<Storage, CliError> {
+
+    let storage = open(Connection::open(path)?, path)?;
+    storage.initialize()?;
+
+    Ok(storage)
+}
+
+/// Opens an existing database without write access. The schema is not
+/// created or migrated, so writes fail with a read-only error.
+pub fn connect_readonly(path: &str) -> Result<Storage, CliError> {
+
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    match Connection::open_with_flags(path, flags) {
+        Ok(conn) => open(conn, path),
+        Err(err) => Err(CliError(format!("failed to open {} read-only: {}", path, err))),
+    }
+}
+
+fn open(conn: Connection, path: &str) -> Result<Storage, CliError> {
+
+    // opening is lazy, the header is only read by the first query
+    if let Err(rusqlite::Error::SqliteFailure(err, _)) = conn.query_row("pragma schema_version", [], |row| row.get::<_, i64>(0)) {
+        if err.code == ErrorCode::NotADatabase {
+            return Err(CliError(format!("{} is not a valid htrackr database, use --db to open a different file", path)));
+        }
+    }
+
+    Ok(Storage {
+        conn,
+        explain: None,
+        strict_dates: false,
+        ids: RefCell::new(HashMap::new()),
+        pending: RefCell::new(vec![]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+    use crate::export;
+
+    #[test]
+    fn test_connect_readonly() {
+        let path = format!("./db_test/{}.db", Uuid::new_v4());
+        let storage = connect(&path).unwrap();
+        storage.create_habit("read").unwrap();
+        drop(storage);
+
+        let storage = connect_readonly(&path).unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["read"]);
+        let err = storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap_err();
+        assert!(err.0.contains("read-only"), "{}", err.0);
+        assert!(storage.create_habit("gym").is_err());
+
+        assert!(connect_readonly("./db_test/missing.db").is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+
+        let statements = Rc::new(RefCell::new(vec![]));
+        let log = statements.clone();
+        storage.set_explain(Box::new(move |sql| log.borrow_mut().push(sql.to_owned())));
+
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+
+        let statements = statements.borrow();
+        assert!(!statements.is_empty());
+        assert!(statements.iter().any(|sql| sql.starts_with("insert into habit_entries")));
+    }
+    #[test]
+    fn test_create_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        let exists = storage.habit_exists("read").unwrap();
+        assert!(exists);
+    }
+
+    #[test]
+    fn test_delete_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.delete_habit("read").unwrap();
+        let exists = storage.habit_exists("read").unwrap();
+        assert!(!exists);
+    }
+
+    #[test]
+    fn test_rename_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        storage.rename_habit("abcde", "asdfgh").unwrap();
+
+        let exists = storage.habit_exists("asdfgh").unwrap();
+        assert!(exists);
+        let exists = storage.habit_exists("abcde").unwrap();
+        assert!(!exists);
+    }
+
+    #[test]
+    fn test_delete_trashed() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 1 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &today).unwrap();
+        storage.soft_delete("read", &today).unwrap();
+        storage.create_habit("read").unwrap();
+        storage.soft_delete("read", &today).unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.soft_delete("gym", &today).unwrap();
+
+        storage.delete_habit("read").unwrap();
+        assert_eq!(storage.list_trashed().unwrap(), vec![("gym".to_owned(), today.clone())]);
+        assert!(storage.delete_habit("read").is_err());
+
+        // an active habit goes first, the trash is left alone
+        storage.create_habit("gym").unwrap();
+        storage.delete_habit("gym").unwrap();
+        assert_eq!(storage.list_trashed().unwrap().len(), 1);
+
+        assert_eq!(storage.purge_trash().unwrap(), 1);
+        assert!(storage.list_trashed().unwrap().is_empty());
+        assert_eq!(storage.recompute_derived().unwrap().anomalies, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_savepoint() {
+        let storage = connect_test().unwrap();
+
+        storage.with_transaction(|s| {
+            s.with_savepoint("one", |s| s.create_habit("read"))?;
+            let failed = s.with_savepoint("two", |s| {
+                s.create_habit("gym")?;
+                s.create_habit("read")
+            });
+            assert!(failed.is_err());
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(storage.habit_list().unwrap(), vec!["read"]);
+    }
+
+    #[test]
+    fn test_after_commit() {
+        let storage = connect_test().unwrap();
+        let ran = Rc::new(RefCell::new(vec![]));
+        let push = |label: &'static str| {
+            let ran = ran.clone();
+            Box::new(move || ran.borrow_mut().push(label)) as Box<dyn FnOnce()>
+        };
+
+        storage.after_commit(push("now"));
+        assert_eq!(*ran.borrow(), vec!["now"]);
+
+        storage.with_transaction(|s| {
+            s.after_commit(push("committed"));
+            let _ = s.with_savepoint("line", |s| {
+                s.after_commit(push("rolled back line"));
+                Err::<(), _>(CliError::new("failed"))
+            });
+            assert_eq!(ran.borrow().len(), 1);
+            Ok(())
+        }).unwrap();
+        assert_eq!(*ran.borrow(), vec!["now", "committed"]);
+
+        let _ = storage.with_transaction(|s| {
+            s.after_commit(push("rolled back"));
+            Err::<(), _>(CliError::new("failed"))
+        });
+        assert_eq!(*ran.borrow(), vec!["now", "committed"]);
+    }
+
+    #[test]
+    fn test_id_cache() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+
+        let id = storage.resolve_habit("read").unwrap();
+        assert_eq!(storage.ids.borrow().get("read"), Some(&id));
+        assert_eq!(storage.resolve_habit("read").unwrap(), id);
+
+        storage.rename_habit("read", "reading").unwrap();
+        assert!(storage.ids.borrow().get("read").is_none());
+        assert!(storage.resolve_habit("read").is_err());
+        assert_eq!(storage.resolve_habit("reading").unwrap(), id);
+
+        storage.create_habit("read").unwrap();
+        assert_ne!(storage.resolve_habit("read").unwrap(), id);
+
+        storage.delete_habit("reading").unwrap();
+        assert!(storage.resolve_habit("reading").is_err());
+    }
+
+    #[test]
+    fn test_rename_collision() {
+        let storage = connect_test().unwrap();
+
+        for name in ["read", "walk", "walk (2)"] {
+            storage.create_habit(name).unwrap();
+        }
+
+        assert!(storage.rename_habit("read", "walk").is_err());
+        assert!(storage.habit_exists("read").unwrap());
+        storage.rename_habit("read", "read").unwrap();
+
+        assert_eq!(storage.rename_habit_auto_suffix("read", "walk").unwrap(), "walk (3)");
+        assert_eq!(storage.habit_list().unwrap(), vec!["walk (3)", "walk", "walk (2)"]);
+        assert_eq!(storage.rename_habit_auto_suffix("walk (3)", "gym").unwrap(), "gym");
+    }
+
+    #[test]
+    fn test_rename_keeps_id_and_entries() {
+        let storage = connect_test().unwrap();
+        let start = Date { year: 2024, month: 3, day: 1 };
+        let end = Date { year: 2024, month: 3, day: 31 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &start).unwrap();
+        storage.mark_habit("read", &end).unwrap();
+
+        let id = storage.resolve_habit("read").unwrap();
+        let days = storage.get_marked_days("read", &start, &end).unwrap();
+
+        storage.rename_habit("read", "reading").unwrap();
+
+        assert_eq!(storage.resolve_habit("reading").unwrap(), id);
+        assert_eq!(storage.get_marked_days("reading", &start, &end).unwrap(), days);
+        assert_eq!(days.len(), 2);
+    }
+
+    #[test]
+    fn test_rename_keeps_metadata() {
+        let storage = connect_test().unwrap();
+        let date = Date { year: 2024, month: 3, day: 1 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &date).unwrap();
+        storage.add_tag("read", "mind").unwrap();
+        storage.set_color("read", Some("blue")).unwrap();
+        storage.set_note("read", &date, "chapter 1").unwrap();
+        storage.set_weekly_goal("read", Some(4)).unwrap();
+        storage.set_interval("read", 2).unwrap();
+        storage.set_created_at("read", &date).unwrap();
+        storage.pause("read", &date.add_days(1)).unwrap();
+
+        storage.rename_habit("read", "reading").unwrap();
+
+        assert_eq!(storage.get_tags("reading").unwrap(), vec!["mind"]);
+        assert_eq!(storage.colors().unwrap().get("reading").cloned(), Some("blue".to_owned()));
+        assert_eq!(storage.get_note("reading", &date).unwrap(), Some("chapter 1".to_owned()));
+        assert_eq!(storage.get_weekly_goal("reading").unwrap(), Some(4));
+        assert_eq!(storage.get_interval("reading").unwrap(), 2);
+        assert_eq!(storage.created_at("reading").unwrap(), Some(date.clone()));
+        assert!(storage.is_paused("reading", &date.add_days(1)).unwrap());
+        assert_eq!(storage.habits_by_tag().unwrap(), vec![(Some("mind".to_owned()), vec!["reading".to_owned()])]);
+    }
+
+    #[test]
+    fn test_list_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        storage.create_habit("asdfgh").unwrap();
+
+        let list = storage.habit_list().unwrap();
+        assert!(list.contains(&String::from("abcde")));
+        assert!(list.contains(&String::from("asdfgh")));
+    }
+
+    #[test]
+    fn test_mark_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        let date1 = Date { year: 2006, month: 6, day: 7 };
+        let date2 = Date { year: 2006, month: 6, day: 9 };
+        assert!(storage.mark_habit("abcde", &date1).unwrap());
+        assert!(storage.mark_habit("abcde", &date2).unwrap());
+        assert!(storage.mark_habit("abcde", &date1).is_err());
+        let days = storage.get_marked_days("abcde", &Date { year: 2006, month: 6, day: 1 }, &Date { year: 2006, month: 6, day: 20 }).unwrap();
+
+        assert!(days.len() == 2);
+        assert!(days.contains(&date1));
+        assert!(days.contains(&date2));
+        assert!(!days.contains(&Date { year: 2006, month: 6, day: 10 }));
+    }
+
+    #[test]
+    fn test_import_entries_csv() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+
+        let text = "habit,date\nread,2024-03-01\nread,2024-03-02\n\"walk, run\",2024-03-02\n\"walk, run\",2024-03-02\n";
+        let entries = export::from_csv(text).unwrap();
+
+        let summary = storage.import_entries(&entries, true).unwrap();
+        assert_eq!(summary, ImportSummary { habits_created: 1, habits_skipped: 1, entries_created: 2, entries_skipped: 2 });
+        assert!(!storage.habit_exists("walk, run").unwrap());
+
+        assert_eq!(storage.import_entries(&entries, false).unwrap(), summary);
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 2);
+        assert_eq!(storage.count_entries("walk, run", None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_mark_habit_idempotent() {
+        let storage = connect_test().unwrap();
+        let date = Date { year: 2006, month: 6, day: 7 };
+
+        storage.create_habit("abcde").unwrap();
+        assert!(storage.mark_habit_idempotent("abcde", &date).unwrap());
+        assert!(!storage.mark_habit_idempotent("abcde", &date).unwrap());
+        assert_eq!(storage.count_entries("abcde", None, None).unwrap(), 1);
+        assert!(storage.mark_habit_idempotent("missing", &date).is_err());
+    }
+
+    #[test]
+    fn test_mark_unhabit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        let date1 = Date { year: 2006, month: 6, day: 7 };
+        let date2 = Date { year: 2006, month: 6, day: 9 };
+        storage.mark_habit("abcde", &date1).unwrap();
+        storage.mark_habit("abcde", &date2).unwrap();
+        storage.unmark_habit("abcde", &date2).unwrap();
+        let days = storage.get_marked_days("abcde", &Date { year: 2006, month: 6, day: 1 }, &Date { year: 2006, month: 6, day: 20 }).unwrap();
+
+        assert!(days.len() == 1);
+        assert!(days.contains(&date1));
+        assert!(!days.contains(&date2));
+    }
+
+    #[test]
+    fn test_first_last_marked() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        assert_eq!(storage.first_marked("abcde").unwrap(), None);
+
+        storage.mark_habit("abcde", &Date { year: 2023, month: 11, day: 30 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2024, month: 2, day: 1 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2024, month: 2, day: 9 }).unwrap();
+
+        assert_eq!(storage.first_marked("abcde").unwrap(), Some(Date { year: 2023, month: 11, day: 30 }));
+        assert_eq!(storage.last_marked("abcde").unwrap(), Some(Date { year: 2024, month: 2, day: 9 }));
+    }
+
+    #[test]
+    fn test_batch_mark() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        storage.create_habit("asdfgh").unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+
+        let entries = vec![
+            ("abcde".to_string(), Date { year: 2006, month: 6, day: 7 }),
+            ("abcde".to_string(), Date { year: 2006, month: 6, day: 8 }),
+            ("asdfgh".to_string(), Date { year: 2006, month: 6, day: 7 }),
+            ("asdfgh".to_string(), Date { year: 2006, month: 6, day: 7 }),
+        ];
+        let summary = storage.batch_mark(&entries).unwrap();
+        assert_eq!((summary.inserted, summary.skipped), (2, 2));
+        assert_eq!(summary.marked, vec![
+            ("abcde".to_string(), Date { year: 2006, month: 6, day: 8 }),
+            ("asdfgh".to_string(), Date { year: 2006, month: 6, day: 7 }),
+        ]);
+
+        let start = Date { year: 2006, month: 6, day: 1 };
+        let end = Date { year: 2006, month: 6, day: 30 };
+        assert_eq!(storage.get_marked_days("abcde", &start, &end).unwrap().len(), 2);
+        assert_eq!(storage.get_marked_days("asdfgh", &start, &end).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_batch_mark_missing_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+
+        let entries = vec![
+            ("abcde".to_string(), Date { year: 2006, month: 6, day: 7 }),
+            ("missing".to_string(), Date { year: 2006, month: 6, day: 7 }),
+        ];
+        assert!(storage.batch_mark(&entries).is_err());
+
+        let days = storage.get_marked_days("abcde", &Date { year: 2006, month: 6, day: 1 }, &Date { year: 2006, month: 6, day: 30 }).unwrap();
+        assert!(days.is_empty());
+    }
+
+    #[test]
+    fn test_all_entries() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.mark_habit("read", &Date { year: 2006, month: 6, day: 9 }).unwrap();
+        storage.mark_habit("read", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+        storage.mark_habit("gym", &Date { year: 2006, month: 6, day: 8 }).unwrap();
+
+        let all = storage.all_entries(None, None).unwrap();
+        let all = all.iter().map(|e| (e.name.as_str(), e.date.day)).collect::<Vec<_>>();
+        assert_eq!(all, vec![("gym", 8), ("read", 7), ("read", 9)]);
+
+        let read = storage.all_entries(Some("read"), None).unwrap();
+        assert_eq!(read.len(), 2);
+        assert!(storage.all_entries(Some("missing"), None).is_err());
+    }
+
+    #[test]
+    fn test_set_position() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("c").unwrap();
+        storage.create_habit("a").unwrap();
+        storage.create_habit("b").unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["c", "a", "b"]);
+
+        storage.set_position("b", 1).unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["b", "c", "a"]);
+
+        storage.set_position("b", 10).unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["c", "a", "b"]);
+
+        storage.set_position("a", 1).unwrap();
+        storage.create_habit("d").unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["a", "c", "b", "d"]);
+
+        assert!(storage.set_position("a", 0).is_err());
+    }
+
+    #[test]
+    fn test_daily_totals() {
+        let storage = connect_test().unwrap();
+        let start = Date { year: 2024, month: 2, day: 28 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("old").unwrap();
+        for name in ["read", "gym", "old"] {
+            storage.mark_habit(name, &start).unwrap();
+        }
+        storage.mark_habit("read", &start.add_days(2)).unwrap();
+        storage.mark_habit("read", &start.add_days(5)).unwrap();
+        storage.soft_delete("old", &start).unwrap();
+
+        assert_eq!(storage.daily_totals(&start, &start.add_days(3)).unwrap(), vec![
+            (Date { year: 2024, month: 2, day: 28 }, 2),
+            (Date { year: 2024, month: 2, day: 29 }, 0),
+            (Date { year: 2024, month: 3, day: 1 }, 1),
+            (Date { year: 2024, month: 3, day: 2 }, 0),
+        ]);
+        assert!(storage.daily_totals(&start.add_days(1), &start).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_marked_set() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        for (month, day) in [(3, 5), (2, 28), (3, 1)] {
+            storage.mark_habit("read", &Date { year: 2024, month, day }).unwrap();
+        }
+
+        let set = storage.marked_set("read", &Date { year: 2024, month: 2, day: 1 }, &Date { year: 2024, month: 3, day: 4 }).unwrap();
+        assert_eq!(set.iter().collect::<Vec<&Date>>(), vec![
+            &Date { year: 2024, month: 2, day: 28 },
+            &Date { year: 2024, month: 3, day: 1 },
+        ]);
+        assert!(set.contains(&Date { year: 2024, month: 3, day: 1 }));
+        assert!(!set.contains(&Date { year: 2024, month: 3, day: 5 }));
+    }
+
+    #[test]
+    fn test_invalid_dates() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+        let id = storage.resolve_habit("read").unwrap();
+        for date in ["2024-13-01", "yesterday", "2024-3-2"] {
+            storage.execute("insert into habit_entries (habit_id, date) values (?1, ?2)", params![id, date]).unwrap();
+        }
+
+        let invalid = storage.find_invalid_dates().unwrap();
+        assert_eq!(invalid, vec![
+            ("read".to_owned(), "2024-13-01".to_owned()),
+            ("read".to_owned(), "2024-3-2".to_owned()),
+            ("read".to_owned(), "yesterday".to_owned()),
+        ]);
+
+        assert_eq!(storage.remove_invalid_dates().unwrap(), 3);
+        assert!(storage.find_invalid_dates().unwrap().is_empty());
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_habit_list_order() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("walk").unwrap();
+        storage.create_habit("Read").unwrap();
+        storage.create_habit("gym").unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["walk", "Read", "gym"]);
+
+        // ties on position fall back to the name, ignoring case
+        storage.execute("update habits set position = 1", []).unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["gym", "Read", "walk"]);
+    }
+
+    #[test]
+    fn test_unmark_range() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        for day in 1..=10 {
+            storage.mark_habit("abcde", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+
+        let removed = storage.unmark_range("abcde", &Date { year: 2024, month: 3, day: 3 }, &Date { year: 2024, month: 3, day: 5 }, DayFilter::All).unwrap();
+        assert_eq!(removed, 3);
+
+        let days = storage.get_marked_days("abcde", &Date { year: 2024, month: 3, day: 1 }, &Date { year: 2024, month: 3, day: 31 }).unwrap();
+        assert_eq!(days.iter().map(|d| d.day).collect::<Vec<_>>(), vec![1, 2, 6, 7, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_unmark_range_malformed() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        for day in 1..=10 {
+            storage.mark_habit("abcde", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        let id = storage.resolve_habit("abcde").unwrap();
+        storage.execute("insert into habit_entries (habit_id, date) values (?1, '2024-03-02x')", params![id]).unwrap();
+
+        // the malformed row shares the first pages with valid ones
+        let removed = storage.unmark_range_paged("abcde", &Date { year: 2024, month: 3, day: 1 }, &Date { year: 2024, month: 3, day: 31 }, DayFilter::All, 2).unwrap();
+        assert_eq!(removed, 10);
+        assert_eq!(storage.find_invalid_dates().unwrap(), vec![("abcde".to_owned(), "2024-03-02x".to_owned())]);
+    }
+
+    #[test]
+    fn test_unmark_range_weekends() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        for day in 1..=10 {
+            storage.mark_habit("abcde", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+
+        // 2024-03-02/03 and 2024-03-09/10 are weekends
+        let removed = storage.unmark_range("abcde", &Date { year: 2024, month: 3, day: 1 }, &Date { year: 2024, month: 3, day: 31 }, DayFilter::Weekends).unwrap();
+        assert_eq!(removed, 4);
+
+        let days = storage.get_marked_days("abcde", &Date { year: 2024, month: 3, day: 1 }, &Date { year: 2024, month: 3, day: 31 }).unwrap();
+        assert_eq!(days.iter().map(|d| d.day).collect::<Vec<_>>(), vec![1, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_current_streak() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        let today = Date { year: 2024, month: 3, day: 2 };
+        assert_eq!(storage.current_streak("abcde", &today).unwrap(), 0);
+
+        storage.mark_habit("abcde", &Date { year: 2024, month: 2, day: 27 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2024, month: 2, day: 29 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+        assert_eq!(storage.current_streak("abcde", &today).unwrap(), 2);
+
+        storage.mark_habit("abcde", &Date { year: 2024, month: 3, day: 2 }).unwrap();
+        assert_eq!(storage.current_streak("abcde", &today).unwrap(), 3);
+
+        let later = Date { year: 2024, month: 3, day: 4 };
+        assert_eq!(storage.current_streak("abcde", &later).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_clear_all() {
+        let storage = connect_test().unwrap();
 
         storage.create_habit("abcde").unwrap();
-        storage.rename_habit("abcde", "asdfgh").unwrap();
+        storage.create_habit("asdfgh").unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 }).unwrap();
 
-        let exists = storage.habit_exists("asdfgh").unwrap();
-        assert!(exists);
-        let exists = storage.habit_exists("abcde").unwrap();
-        assert!(!exists);
+        storage.clear_all().unwrap();
+
+        assert!(storage.habit_list().unwrap().is_empty());
+        let entries: i32 = storage.query_row("select count(1) from habit_entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(entries, 0);
     }
 
     #[test]
-    fn test_list_habit() {
+    fn test_habit_color() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        assert_eq!(storage.colors().unwrap().get("abcde").cloned(), None);
+
+        storage.set_color("abcde", Some("green")).unwrap();
+        assert_eq!(storage.colors().unwrap().get("abcde").cloned(), Some("green".to_string()));
+
+        assert!(storage.set_color("abcde", Some("chartreuse")).is_err());
+        assert_eq!(storage.colors().unwrap().get("abcde").cloned(), Some("green".to_string()));
+
+        storage.set_color("abcde", None).unwrap();
+        assert_eq!(storage.colors().unwrap().get("abcde").cloned(), None);
+    }
+
+    #[test]
+    fn test_count_entries() {
         let storage = connect_test().unwrap();
 
         storage.create_habit("abcde").unwrap();
         storage.create_habit("asdfgh").unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 5, day: 31 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 9 }).unwrap();
+        storage.mark_habit("asdfgh", &Date { year: 2006, month: 6, day: 8 }).unwrap();
+
+        let from = Date { year: 2006, month: 6, day: 1 };
+        let to = Date { year: 2006, month: 6, day: 7 };
+
+        assert_eq!(storage.count_entries("abcde", None, None).unwrap(), 3);
+        assert_eq!(storage.count_entries("abcde", Some(&from), Some(&to)).unwrap(), 1);
+        assert_eq!(storage.count_entries("abcde", Some(&from), None).unwrap(), 2);
+        assert_eq!(storage.count_entries("abcde", None, Some(&to)).unwrap(), 2);
+        assert_eq!(storage.count_entries("asdfgh", None, None).unwrap(), 1);
+    }
 
-        let list = storage.habit_list().unwrap();
-        assert!(list.contains(&String::from("abcde")));
-        assert!(list.contains(&String::from("asdfgh")));
+    #[test]
+    fn test_backup_to() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+
+        let path = format!("./db_test/{}.backup.db", Uuid::new_v4());
+        storage.backup_to(&path).unwrap();
+
+        let copy = connect(&path).unwrap();
+        assert_eq!(copy.habit_list().unwrap(), vec!["abcde"]);
+        assert_eq!(copy.count_entries("abcde", None, None).unwrap(), 1);
+        assert_eq!(copy.resolve_habit("abcde").unwrap(), storage.resolve_habit("abcde").unwrap());
     }
 
     #[test]
-    fn test_mark_habit() {
+    fn test_export_since() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        for day in [1, 14, 15, 20] {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        storage.mark_habit("gym", &Date { year: 2024, month: 3, day: 2 }).unwrap();
+
+        let since = Date { year: 2024, month: 3, day: 15 };
+        let habits = storage.export_all(true, Some(&since)).unwrap();
+        assert_eq!(habits.len(), 2);
+        assert_eq!(habits[0].entries, vec![since.clone(), Date { year: 2024, month: 3, day: 20 }]);
+        assert!(habits[1].entries.is_empty());
+
+        assert_eq!(storage.all_entries(None, Some(&since)).unwrap().len(), 2);
+
+        let mut out = vec![];
+        storage.stream_export(&mut out, StreamFormat::Json, None, Some(&since)).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", export::to_json(&habits).unwrap()));
+    }
+
+    #[test]
+    fn test_export_without_entries() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+
+        let habits = storage.export_all(false, None).unwrap();
+        assert_eq!(habits.len(), 1);
+        assert_eq!(habits[0].name, "read");
+        assert!(habits[0].entries.is_empty());
+
+        let json = crate::export::to_json(&habits).unwrap();
+        assert!(json.contains("\"entries\":[]"));
+
+        assert_eq!(storage.export_all(true, None).unwrap()[0].entries.len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_date() {
+        let mut storage = connect_test().unwrap();
+        let start = Date { year: 2024, month: 3, day: 1 };
+        let end = Date { year: 2024, month: 3, day: 31 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &start).unwrap();
+        let id = storage.resolve_habit("read").unwrap();
+        storage.execute("insert into habit_entries (habit_id, date) values (?1, '2024-03-1x')", params![id]).unwrap();
+
+        storage.execute("insert into habit_entries (habit_id, date) values (?1, '2024-02-2x')", params![id]).unwrap();
+        let today = Date { year: 2024, month: 3, day: 2 };
+
+        assert_eq!(storage.get_marked_days("read", &start, &end).unwrap(), vec![start.clone()]);
+        assert_eq!(storage.get_marked_days_paged("read", &start, &end, 0, 10).unwrap(), vec![start.clone()]);
+        assert_eq!(storage.all_entries(None, None).unwrap().len(), 1);
+        assert_eq!(storage.current_streak("read", &today).unwrap(), 1);
+        assert_eq!(storage.longest_streak("read").unwrap(), 1);
+        assert_eq!(storage.first_marked("read").unwrap(), Some(start.clone()));
+        assert_eq!(storage.last_marked("read").unwrap(), Some(start.clone()));
+
+        storage.set_strict_dates(true);
+        let err = storage.get_marked_days("read", &start, &end).err().unwrap();
+        assert!(err.0.starts_with("malformed entry of habit read"));
+        assert!(storage.get_marked_days_paged("read", &start, &end, 0, 10).is_err());
+        assert!(storage.all_entries(None, None).is_err());
+        assert!(storage.current_streak("read", &today).is_err());
+        assert!(storage.longest_streak("read").is_err());
+        assert!(storage.first_marked("read").is_err());
+    }
+
+    #[test]
+    fn test_marked_days_in_range() {
+        let storage = connect_test().unwrap();
+        let start = Date { year: 2024, month: 3, day: 1 };
+        let end = Date { year: 2024, month: 3, day: 31 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        for day in [1, 5, 31] {
+            storage.mark_habit("read", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        storage.mark_habit("gym", &Date { year: 2024, month: 3, day: 2 }).unwrap();
+        storage.mark_habit("gym", &Date { year: 2024, month: 4, day: 1 }).unwrap();
+
+        let days = storage.marked_days_in_range(&start, &end).unwrap();
+        for name in storage.habit_list().unwrap() {
+            let mut expected = storage.get_marked_days(&name, &start, &end).unwrap();
+            expected.sort_by_key(|d| d.day);
+            assert_eq!(days.get(&name).cloned().unwrap_or_default(), expected);
+        }
+        assert!(!days.contains_key("walk"));
+    }
+
+    #[test]
+    fn test_habit_attributes() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.set_color("read", Some("red")).unwrap();
+        storage.set_weekly_goal("read", Some(3)).unwrap();
+        storage.pause("gym", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+        storage.pause("walk", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+        storage.resume("walk", &Date { year: 2024, month: 3, day: 5 }).unwrap();
+
+        let colors = storage.colors().unwrap();
+        let paused = storage.paused_habits(&today).unwrap();
+        let goals = storage.weekly_goals().unwrap();
+        assert_eq!(colors, HashMap::from([("read".to_owned(), "red".to_owned())]));
+        assert_eq!(goals, HashMap::from([("read".to_owned(), 3)]));
+        assert_eq!(paused, HashSet::from(["gym".to_owned()]));
+        for name in storage.habit_list().unwrap() {
+            assert_eq!(paused.contains(&name), storage.is_paused(&name, &today).unwrap());
+            assert_eq!(goals.get(&name).copied(), storage.get_weekly_goal(&name).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_vacuum_into() {
         let storage = connect_test().unwrap();
 
         storage.create_habit("abcde").unwrap();
-        let date1 = Date { year: 2006, month: 6, day: 7 };
-        let date2 = Date { year: 2006, month: 6, day: 9 };
-        storage.mark_habit("abcde", &date1).unwrap();
-        storage.mark_habit("abcde", &date2).unwrap();
-        let days = storage.get_marked_days("abcde", &Date { year: 2006, month: 6, day: 1 }, &Date { year: 2006, month: 6, day: 20 }).unwrap();
+        storage.create_habit("fghij").unwrap();
+        storage.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 }).unwrap();
 
-        assert!(days.len() == 2);
-        assert!(days.contains(&date1));
-        assert!(days.contains(&date2));
-        assert!(!days.contains(&Date { year: 2006, month: 6, day: 10 }));
+        let path = format!("./db_test/{}.compact.db", Uuid::new_v4());
+        storage.vacuum_into(&path).unwrap();
+        assert!(storage.vacuum_into(&path).is_err());
+
+        let copy = connect(&path).unwrap();
+        assert_eq!(copy.habit_list().unwrap(), storage.habit_list().unwrap());
+        assert_eq!(copy.count_entries("abcde", None, None).unwrap(), 1);
     }
 
     #[test]
-    fn test_mark_unhabit() {
+    fn test_duplicate_entries_migration() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+
+        // as written before the unique index
+        storage.execute("drop index habit_entries_habit_date", []).unwrap();
+        let id = storage.resolve_habit("read").unwrap();
+        for date in ["2024-03-01", "2024-03-01", "2024-03-02"] {
+            storage.execute("insert into habit_entries (habit_id, date) values (?1, ?2)", params![id, date]).unwrap();
+        }
+
+        storage.initialize().unwrap();
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 2);
+        assert!(storage.execute("insert into habit_entries (habit_id, date) values (?1, '2024-03-02')", params![id]).is_err());
+    }
+
+    #[test]
+    fn test_recompute_derived() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 1 }).unwrap();
+        storage.mark_habit("read", &Date { year: 2024, month: 3, day: 2 }).unwrap();
+        storage.add_tag("read", "mind").unwrap();
+
+        assert_eq!(storage.recompute_derived().unwrap(), ReplaySummary { entries: 2, anomalies: vec![] });
+
+        // older databases were written without foreign key checks
+        storage.conn.pragma_update(None, "foreign_keys", false).unwrap();
+        storage.execute("insert into habit_entries (habit_id, date) values ('hbt_gone', '2024-03-01')", []).unwrap();
+        storage.execute("insert into habit_tags (habit_id, tag) values ('hbt_gone', 'mind')", []).unwrap();
+
+        let summary = storage.recompute_derived().unwrap();
+        assert_eq!(summary.entries, 3);
+        assert_eq!(summary.anomalies, vec!["1 entries of missing habit hbt_gone", "tags of missing habit hbt_gone"]);
+    }
+
+    #[test]
+    fn test_stream_export() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.create_habit("Read, books").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.set_color("gym", Some("red")).unwrap();
+        for day in 1..=20 {
+            storage.mark_habit("walk", &Date { year: 2024, month: 3, day }).unwrap();
+        }
+        storage.mark_habit("Read, books", &Date { year: 2024, month: 2, day: 29 }).unwrap();
+
+        let stream = |format, name| {
+            let mut out = vec![];
+            storage.stream_export(&mut out, format, name, None).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let mut habits = storage.export_all(true, None).unwrap();
+        assert_eq!(stream(StreamFormat::Json, None), format!("{}\n", export::to_json(&habits).unwrap()));
+        assert_eq!(stream(StreamFormat::Csv, None), export::to_csv(&storage.all_entries(None, None).unwrap()).unwrap());
+        assert_eq!(stream(StreamFormat::Tsv, None), export::to_tsv(&storage.all_entries(None, None).unwrap()).unwrap());
+
+        habits.retain(|h| h.name == "gym");
+        assert_eq!(stream(StreamFormat::Json, Some("gym")), format!("{}\n", export::to_json(&habits).unwrap()));
+        assert!(storage.stream_export(&mut vec![], StreamFormat::Csv, Some("none"), None).is_err());
+    }
+
+    #[test]
+    fn test_stream_export_malformed() {
+        let storage = connect_test().unwrap();
+        storage.create_habit("x").unwrap();
+        storage.create_habit("y").unwrap();
+        let id = storage.resolve_habit("x").unwrap();
+        storage.execute("insert into habit_entries (habit_id, date) values (?1, 'garbage')", params![id]).unwrap();
+
+        let mut out = vec![];
+        storage.stream_export(&mut out, StreamFormat::Json, None, None).unwrap();
+
+        let habits = storage.export_all(true, None).unwrap();
+        assert_eq!(habits.len(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), format!("{}\n", export::to_json(&habits).unwrap()));
+    }
+
+    #[test]
+    fn test_habit_defaults() {
+        let storage = connect_test().unwrap();
+
+        storage.set_setting("defaults.tag", "health").unwrap();
+        storage.set_setting("defaults.schedule", "weekdays").unwrap();
+        assert!(storage.set_setting("defaults.schedule", "monthly").is_err());
+
+        let defaults = storage.load_config().unwrap().habit_defaults;
+        storage.create_habit("run").unwrap();
+        storage.apply_habit_defaults("run", &defaults).unwrap();
+        assert_eq!(storage.get_tags("run").unwrap(), vec!["health"]);
+        assert_eq!(storage.get_schedule("run").unwrap(), Schedule::Weekdays);
+
+        storage.set_setting("defaults.tag", "").unwrap();
+        assert_eq!(storage.load_config().unwrap().habit_defaults.tag, None);
+    }
+
+    #[test]
+    fn test_habit_id_prefix() {
         let storage = connect_test().unwrap();
 
         storage.create_habit("abcde").unwrap();
-        let date1 = Date { year: 2006, month: 6, day: 7 };
-        let date2 = Date { year: 2006, month: 6, day: 9 };
-        storage.mark_habit("abcde", &date1).unwrap();
-        storage.mark_habit("abcde", &date2).unwrap();
-        storage.unmark_habit("abcde", &date2).unwrap();
-        let days = storage.get_marked_days("abcde", &Date { year: 2006, month: 6, day: 1 }, &Date { year: 2006, month: 6, day: 20 }).unwrap();
+        assert!(storage.resolve_habit("abcde").unwrap().starts_with("hbt_"));
 
-        assert!(days.len() == 1);
-        assert!(days.contains(&date1));
-        assert!(!days.contains(&date2));
+        storage.set_setting("id.prefix", "h-").unwrap();
+        storage.set_setting("id.slug", "true").unwrap();
+        storage.create_habit("Read Books").unwrap();
+        storage.create_habit("read books!").unwrap();
+
+        let id1 = storage.resolve_habit("Read Books").unwrap();
+        let id2 = storage.resolve_habit("read books!").unwrap();
+        assert!(id1.starts_with("h-read-books-"));
+        assert!(id2.starts_with("h-read-books-"));
+        assert_ne!(id1, id2);
+
+        assert!(storage.set_setting("id.slug", "maybe").is_err());
+        assert!(storage.set_setting("unknown", "1").is_err());
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("Read Books"), "read-books");
+        assert_eq!(slug("  gym -- (am) "), "gym-am");
+        assert_eq!(slug("🏃"), "habit");
+    }
+
+    #[test]
+    fn test_get_marked_days_paged() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+        let start = Date { year: 2023, month: 12, day: 1 };
+        let end = Date { year: 2024, month: 2, day: 29 };
+        let entries = crate::date::days_between(&start, &end).unwrap()
+            .into_iter()
+            .step_by(3)
+            .map(|d| ("abcde".to_owned(), d))
+            .collect::<Vec<_>>();
+        storage.batch_mark(&entries).unwrap();
+
+        let all = storage.get_marked_days("abcde", &start, &end).unwrap();
+
+        let mut paged = vec![];
+        let mut offset = 0;
+        loop {
+            let page = storage.get_marked_days_paged("abcde", &start, &end, offset, 7).unwrap();
+            assert!(page.len() <= 7);
+            if page.is_empty() {
+                break;
+            }
+            offset += page.len();
+            paged.extend(page);
+        }
+
+        assert_eq!(paged.len(), all.len());
+        assert!(all.iter().all(|d| paged.contains(d)));
+    }
+
+    #[test]
+    fn test_connect_invalid_file() {
+        std::fs::create_dir_all("./db_test/").unwrap();
+        let path = format!("./db_test/{}.txt", Uuid::new_v4());
+        std::fs::write(&path, "this is not a database, just some notes that are long enough to fill a header\n".repeat(4)).unwrap();
+
+        let err = connect(&path).err().unwrap();
+        assert!(err.to_string().contains("is not a valid htrackr database"));
+    }
+
+    #[test]
+    fn test_with_transaction_rollback() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+
+        let result: Result<(), CliError> = storage.with_transaction(|s| {
+            s.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 })?;
+            s.create_habit("asdfgh")?;
+            Err(CliError::new("failed"))
+        });
+        assert!(result.is_err());
+
+        assert_eq!(storage.count_entries("abcde", None, None).unwrap(), 0);
+        assert!(!storage.habit_exists("asdfgh").unwrap());
+
+        let result = storage.with_transaction(|s| {
+            s.mark_habit("abcde", &Date { year: 2006, month: 6, day: 7 })?;
+            s.with_transaction(|s| s.create_habit("asdfgh"))?;
+            Ok(1)
+        });
+        assert_eq!(result.unwrap(), 1);
+
+        assert_eq!(storage.count_entries("abcde", None, None).unwrap(), 1);
+        assert!(storage.habit_exists("asdfgh").unwrap());
+    }
+
+    #[test]
+    fn test_import_dry_run() {
+        let source = connect_test().unwrap();
+        source.create_habit("read").unwrap();
+        source.create_habit("gym").unwrap();
+        source.mark_habit("read", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+        source.mark_habit("read", &Date { year: 2006, month: 6, day: 8 }).unwrap();
+        source.mark_habit("gym", &Date { year: 2006, month: 6, day: 8 }).unwrap();
+        let habits = source.export_all(true, None).unwrap();
+
+        let storage = connect_test().unwrap();
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &Date { year: 2006, month: 6, day: 7 }).unwrap();
+
+        let expected = ImportSummary {
+            habits_created: 1,
+            habits_skipped: 1,
+            entries_created: 2,
+            entries_skipped: 1,
+        };
+
+        let summary = storage.import(&habits, true).unwrap();
+        assert_eq!(summary, expected);
+        assert_eq!(storage.habit_list().unwrap(), vec!["read"]);
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 1);
+
+        let summary = storage.import(&habits, false).unwrap();
+        assert_eq!(summary, expected);
+        assert_eq!(storage.habit_list().unwrap(), vec!["read", "gym"]);
+        assert_eq!(storage.count_entries("read", None, None).unwrap(), 2);
+        assert_eq!(storage.resolve_habit("gym").unwrap(), source.resolve_habit("gym").unwrap());
+
+        // inside an open transaction, like in a batch
+        let storage = connect_test().unwrap();
+        storage.with_transaction(|s| {
+            s.create_habit("walk")?;
+            assert_eq!(s.import(&habits, true)?, ImportSummary { habits_created: 2, habits_skipped: 0, entries_created: 3, entries_skipped: 0 });
+            assert_eq!(s.habit_list()?, vec!["walk"]);
+            s.import_entries(&[("read".to_owned(), Date { year: 2006, month: 6, day: 7 })], false)?;
+            Ok(())
+        }).unwrap();
+        assert_eq!(storage.habit_list().unwrap(), vec!["walk", "read"]);
+    }
+
+    #[test]
+    fn test_resolve_habit() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("abcde").unwrap();
+
+        let id = storage.resolve_habit("abcde").unwrap();
+        assert!(id.starts_with("hbt_"));
+
+        let err = storage.resolve_habit("missing").err().unwrap();
+        assert_eq!(err.to_string(), "habit missing not found");
+    }
+
+    #[test]
+    fn test_not_found_suggestion() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("meditate").unwrap();
+
+        let err = storage.resolve_habit("raed").err().unwrap();
+        assert_eq!(err.to_string(), "habit raed not found, did you mean 'read'?");
+
+        let err = storage.resolve_habit("meditat").err().unwrap();
+        assert_eq!(err.to_string(), "habit meditat not found, did you mean 'meditate'?");
+
+        let err = storage.resolve_habit("swim").err().unwrap();
+        assert_eq!(err.to_string(), "habit swim not found");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("read", "read"), 0);
+        assert_eq!(edit_distance("read", "reed"), 1);
+        assert_eq!(edit_distance("read", "reads"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("läuft", "lauft"), 1);
+    }
+
+    #[test]
+    fn test_habits_by_tag() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.create_habit("sleep").unwrap();
+        storage.add_tag("read", "mind").unwrap();
+        storage.add_tag("gym", "body").unwrap();
+        storage.add_tag("walk", "body").unwrap();
+        storage.add_tag("walk", "mind").unwrap();
+        storage.add_tag("walk", "mind").unwrap();
+
+        assert_eq!(storage.get_tags("walk").unwrap(), vec!["body", "mind"]);
+
+        let groups = storage.habits_by_tag().unwrap();
+        assert_eq!(groups, vec![
+            (Some("body".to_owned()), vec!["gym".to_owned(), "walk".to_owned()]),
+            (Some("mind".to_owned()), vec!["read".to_owned(), "walk".to_owned()]),
+            (None, vec!["sleep".to_owned()]),
+        ]);
+
+        storage.remove_tag("walk", "mind").unwrap();
+        assert!(storage.remove_tag("walk", "mind").is_err());
+        assert_eq!(storage.get_tags("walk").unwrap(), vec!["body"]);
+    }
+
+    #[test]
+    fn test_rename_and_delete_tag() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.add_tag("read", "hobby").unwrap();
+        storage.add_tag("walk", "hobby").unwrap();
+        storage.add_tag("walk", "health").unwrap();
+
+        assert_eq!(storage.rename_tag("hobby", "health").unwrap(), 2);
+        assert_eq!(storage.get_tags("read").unwrap(), vec!["health"]);
+        assert_eq!(storage.get_tags("walk").unwrap(), vec!["health"]);
+        assert_eq!(storage.rename_tag("hobby", "fun").unwrap(), 0);
+
+        assert_eq!(storage.delete_tag("health").unwrap(), 2);
+        assert!(storage.get_tags("walk").unwrap().is_empty());
+        assert!(storage.habit_exists("walk").unwrap());
+    }
+
+    #[test]
+    fn test_completed_on() {
+        let storage = connect_test().unwrap();
+        let date = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.mark_habit("read", &date).unwrap();
+        storage.mark_habit("gym", &date).unwrap();
+        storage.mark_habit("walk", &date.add_days(1)).unwrap();
+
+        assert_eq!(storage.completed_on(&date).unwrap(), 2);
+        assert_eq!(storage.completed_on(&date.sub_days(1)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_notes() {
+        let storage = connect_test().unwrap();
+        let date = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        assert_eq!(storage.get_note("read", &date).unwrap(), None);
+        assert!(storage.set_note("read", &date, "chapter 1").is_err());
+
+        storage.mark_habit("read", &date).unwrap();
+        assert_eq!(storage.get_note("read", &date).unwrap(), None);
+
+        storage.set_note("read", &date, "chapter 1").unwrap();
+        storage.set_note("read", &date, "chapter 2").unwrap();
+        assert_eq!(storage.get_note("read", &date).unwrap(), Some("chapter 2".to_owned()));
+    }
+
+    #[test]
+    fn test_ascii_only_names() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("run 🏃").unwrap();
+
+        storage.set_setting("names.ascii_only", "true").unwrap();
+        assert!(storage.create_habit("swim 🏊").is_err());
+        assert!(!storage.habit_exists("swim 🏊").unwrap());
+        assert!(storage.rename_habit("run 🏃", "lauf 🏃").is_err());
+
+        storage.create_habit("swim").unwrap();
+        storage.rename_habit("run 🏃", "run").unwrap();
+    }
+
+    #[test]
+    fn test_interval_streak() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("gym").unwrap();
+        assert_eq!(storage.get_interval("gym").unwrap(), 1);
+        assert!(storage.set_interval("gym", 0).is_err());
+
+        for n in [1, 3, 5, 7, 9] {
+            storage.mark_habit("gym", &today.sub_days(n)).unwrap();
+        }
+        assert_eq!(storage.current_streak("gym", &today).unwrap(), 1);
+        assert_eq!(storage.longest_streak("gym").unwrap(), 1);
+
+        storage.set_interval("gym", 2).unwrap();
+        assert_eq!(storage.current_streak("gym", &today).unwrap(), 5);
+        assert_eq!(storage.current_streak("gym", &today.add_days(1)).unwrap(), 5);
+        assert_eq!(storage.current_streak("gym", &today.add_days(2)).unwrap(), 0);
+        assert_eq!(storage.longest_streak("gym").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_most_consistent() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.create_habit("gym").unwrap();
+        storage.create_habit("walk").unwrap();
+        storage.set_created_at("read", &today.sub_days(9)).unwrap();
+        storage.set_created_at("gym", &today.sub_days(1)).unwrap();
+        storage.set_created_at("walk", &today.sub_days(9)).unwrap();
+
+        // read: 4 old marks, no streak
+        for n in [5, 6, 7, 8] {
+            storage.mark_habit("read", &today.sub_days(n)).unwrap();
+        }
+        // gym: 2 of 2 days
+        storage.mark_habit("gym", &today).unwrap();
+        storage.mark_habit("gym", &today.sub_days(1)).unwrap();
+        // walk: 3 day streak
+        for n in [0, 1, 2] {
+            storage.mark_habit("walk", &today.sub_days(n)).unwrap();
+        }
+
+        let names = |metric| storage.most_consistent(metric, 5, &today).unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<String>>();
+
+        assert_eq!(names(Metric::Streak), vec!["walk", "gym", "read"]);
+        assert_eq!(names(Metric::Rate), vec!["gym", "read", "walk"]);
+        assert_eq!(names(Metric::Count), vec!["read", "walk", "gym"]);
+
+        let top = storage.most_consistent(Metric::Rate, 1, &today).unwrap();
+        assert_eq!(top, vec![("gym".to_owned(), 1.0)]);
+    }
+
+    #[test]
+    fn test_pause_keeps_streak() {
+        let storage = connect_test().unwrap();
+        let start = Date { year: 2024, month: 3, day: 1 };
+
+        storage.create_habit("gym").unwrap();
+        for n in 0..5 {
+            storage.mark_habit("gym", &start.add_days(n)).unwrap();
+        }
+
+        // paused from the 6th to the 8th
+        storage.pause("gym", &start.add_days(5)).unwrap();
+        assert!(storage.pause("gym", &start.add_days(6)).is_err());
+        assert!(storage.is_paused("gym", &start.add_days(7)).unwrap());
+        assert_eq!(storage.current_streak("gym", &start.add_days(7)).unwrap(), 5);
+
+        storage.resume("gym", &start.add_days(8)).unwrap();
+        assert!(!storage.is_paused("gym", &start.add_days(8)).unwrap());
+        assert!(storage.resume("gym", &start.add_days(8)).is_err());
+
+        storage.mark_habit("gym", &start.add_days(8)).unwrap();
+        assert_eq!(storage.current_streak("gym", &start.add_days(8)).unwrap(), 6);
+        assert_eq!(storage.current_streak("gym", &start.add_days(9)).unwrap(), 6);
+        assert_eq!(storage.current_streak("gym", &start.add_days(10)).unwrap(), 0);
+        assert_eq!(storage.longest_streak("gym").unwrap(), 6);
+    }
+
+    #[test]
+    fn test_weekdays_schedule_streak() {
+        let storage = connect_test().unwrap();
+        // friday the 1st to tuesday the 5th of march 2024
+        let friday = Date { year: 2024, month: 3, day: 1 };
+        let monday = friday.add_days(3);
+
+        storage.create_habit("work").unwrap();
+        assert_eq!(storage.get_schedule("work").unwrap(), Schedule::Daily);
+        storage.mark_habit("work", &friday.sub_days(1)).unwrap();
+        storage.mark_habit("work", &friday).unwrap();
+        storage.mark_habit("work", &monday).unwrap();
+
+        assert_eq!(storage.current_streak("work", &monday).unwrap(), 1);
+        assert_eq!(storage.longest_streak("work").unwrap(), 2);
+
+        storage.set_schedule("work", Schedule::Weekdays).unwrap();
+        assert_eq!(storage.get_schedule("work").unwrap(), Schedule::Weekdays);
+        assert_eq!(storage.current_streak("work", &monday).unwrap(), 3);
+        assert_eq!(storage.current_streak("work", &friday.add_days(2)).unwrap(), 2);
+        assert_eq!(storage.current_streak("work", &monday.add_days(1)).unwrap(), 3);
+        assert_eq!(storage.current_streak("work", &monday.add_days(2)).unwrap(), 0);
+        assert_eq!(storage.longest_streak("work").unwrap(), 3);
+
+        assert!(Schedule::parse("mondays").is_err());
+    }
+
+    #[test]
+    fn test_soft_delete_restore() {
+        let storage = connect_test().unwrap();
+        let today = Date { year: 2024, month: 3, day: 10 };
+
+        storage.create_habit("read").unwrap();
+        storage.mark_habit("read", &today).unwrap();
+        storage.soft_delete("read", &today).unwrap();
+
+        assert!(!storage.habit_exists("read").unwrap());
+        assert!(storage.habit_list().unwrap().is_empty());
+        assert!(storage.all_entries(None, None).unwrap().is_empty());
+        assert_eq!(storage.list_trashed().unwrap(), vec![("read".to_owned(), today.clone())]);
+
+        // the name is free again, restoring has to wait until it is
+        storage.create_habit("read").unwrap();
+        assert!(storage.restore("read").is_err());
+        storage.delete_habit("read").unwrap();
+
+        storage.restore("read").unwrap();
+        assert!(storage.list_trashed().unwrap().is_empty());
+        assert_eq!(storage.get_marked_days("read", &today, &today).unwrap(), vec![today]);
+        assert!(storage.restore("read").is_err());
+    }
+
+    #[test]
+    fn test_weekly_goal() {
+        let storage = connect_test().unwrap();
+
+        storage.create_habit("gym").unwrap();
+        assert_eq!(storage.get_weekly_goal("gym").unwrap(), None);
+
+        storage.set_weekly_goal("gym", Some(3)).unwrap();
+        assert_eq!(storage.get_weekly_goal("gym").unwrap(), Some(3));
+        assert!(storage.set_weekly_goal("gym", Some(8)).is_err());
+
+        storage.set_weekly_goal("gym", None).unwrap();
+        assert_eq!(storage.get_weekly_goal("gym").unwrap(), None);
     }
 }
\ No newline at end of file